@@ -1,20 +1,40 @@
 //! [Binance.com](https://binance.com) API.
-use {HttpClient, Query};
-use chrono::Utc;
+#[cfg(feature = "debug")]
+use SignatureDebug;
+use {CcexError, Environment, Host, HttpClient, Permissions, Query, RoundingMode, WhitelistGuard};
+use error::is_insufficient_funds;
+use signer::{HmacSha256Hex, Signer};
+use util::{self, format_amount, format_currency, Case};
+use chrono::{self, DateTime, NaiveDateTime, Utc};
 use failure::Error;
-use hex;
 use serde_json;
-use hmac::{Hmac, Mac};
+use num_traits::Zero;
 use rust_decimal::Decimal as d128;
-use serde::de::DeserializeOwned;
-use sha2::Sha256;
+use serde::de::{self, Deserialize, DeserializeOwned, Deserializer, IgnoredAny, SeqAccess, Visitor};
 use std::fmt::{self, Display, Formatter};
 use http;
 use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
 
-/// Use this as the `host` for REST requests.
+/// Base URL for REST requests. Pass [`host`] to requests rather than this.
 pub const API_HOST: &str = "https://api.binance.com";
 
+/// Use this as the `host` for websocket streams.
+pub const STREAM_HOST: &str = "wss://stream.binance.com:9443";
+
+/// Base URL for REST requests against the testnet.
+pub const TESTNET_API_HOST: &str = "https://testnet.binance.vision";
+
+/// The REST host for `environment`. The sandbox is Binance's spot testnet, which needs its own
+/// credentials.
+pub fn host(environment: Environment) -> Host {
+    match environment {
+        Environment::Production => Host(API_HOST.to_owned()),
+        Environment::Sandbox => Host(TESTNET_API_HOST.to_owned()),
+    }
+}
+
 /// API key and secret. Required for private API calls.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
 pub struct Credential {
@@ -34,12 +54,23 @@ pub struct ExchangeInfo {
     pub products: Vec<ProductInfo>,
 }
 
+impl ExchangeInfo {
+    /// The product whose symbol is `symbol`, e.g. `ETHBTC`. Unlike parsing a `CurrencyPair`, this
+    /// works for every quote currency Binance lists.
+    pub fn currency_pair(&self, symbol: &str) -> Option<CurrencyPair> {
+        self.products
+            .iter()
+            .find(|product| product.symbol.eq_ignore_ascii_case(symbol))
+            .map(ProductInfo::currency_pair)
+    }
+}
+
 /// Symbol info; base, quote, precision, status, etc.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ProductInfo {
-    /// This is `base` and `quote` concatenated. This can't be processed into an actual
-    /// `CurrencyPair` since there's no seperator.
+    /// This is `base` and `quote` concatenated. Use [`ProductInfo::currency_pair`] rather than
+    /// splitting it, since there's no seperator.
     pub symbol: String,
     pub status: SymbolStatus,
     #[serde(rename = "baseAsset")]
@@ -54,6 +85,74 @@ pub struct ProductInfo {
     pub filters: Vec<Filter>,
 }
 
+impl ProductInfo {
+    /// `base` and `quote` as a `CurrencyPair`.
+    pub fn currency_pair(&self) -> CurrencyPair {
+        CurrencyPair(self.base.clone(), self.quote.clone())
+    }
+
+    /// Round `price` to the product's quote precision.
+    pub fn round_price(&self, price: d128, mode: RoundingMode) -> d128 {
+        util::round(price, self.quote_precision, mode)
+    }
+
+    /// Round `quantity` to the product's base precision.
+    pub fn round_quantity(&self, quantity: d128, mode: RoundingMode) -> d128 {
+        util::round(quantity, self.base_precision, mode)
+    }
+
+    /// Check `price` and `quantity` against the product's filters: min/max price and tick size,
+    /// min/max quantity and step size, and minimum notional.
+    ///
+    /// Fails with `CcexError::OrderRejected` saying how the order breaks the filter, and naming
+    /// it the way Binance does. Binance's own rejection only names the filter.
+    pub fn validate_order(&self, price: d128, quantity: d128) -> Result<(), Error> {
+        for filter in &self.filters {
+            let rejection = match *filter {
+                Filter::PriceFilter { min_price, max_price, tick_size } => {
+                    check_range("price", price, min_price, max_price, tick_size)
+                }
+                Filter::LotSize { min_quantity, max_quantity, step_size } => {
+                    check_range("quantity", quantity, min_quantity, max_quantity, step_size)
+                }
+                Filter::MinNotional { min_notional } => {
+                    let notional = price * quantity;
+                    if notional < min_notional {
+                        let minimum = min_notional;
+                        Some(format!("notional {} is below the minimum of {}", notional, minimum))
+                    } else {
+                        None
+                    }
+                }
+            };
+            if let Some(rejection) = rejection {
+                let rejection = format!(
+                    "invalid order for {}: {} ({})",
+                    self.symbol,
+                    rejection,
+                    filter.name()
+                );
+                return Err(CcexError::OrderRejected(rejection).into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Why `value` breaks a min/max/step filter, if it does. Binance disables any part of a filter by
+/// setting it to zero.
+fn check_range(name: &str, value: d128, min: d128, max: d128, step: d128) -> Option<String> {
+    if !min.is_zero() && value < min {
+        Some(format!("{} {} is below the minimum of {}", name, value, min))
+    } else if !max.is_zero() && value > max {
+        Some(format!("{} {} is above the maximum of {}", name, value, max))
+    } else if !step.is_zero() && !((value - min) % step).is_zero() {
+        Some(format!("{} {} isn't a multiple of {}", name, value, step))
+    } else {
+        None
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum SymbolStatus {
@@ -67,7 +166,9 @@ pub enum OrderInstruction {
     Limit,
     LimitMaker,
     Market,
+    StopLoss,
     StopLossLimit,
+    TakeProfit,
     TakeProfitLimit,
 }
 
@@ -78,7 +179,9 @@ impl Display for OrderInstruction {
             Limit => f.write_str("LIMIT"),
             LimitMaker => f.write_str("LIMIT_MAKER"),
             Market => f.write_str("MARKET"),
+            StopLoss => f.write_str("STOP_LOSS"),
             StopLossLimit => f.write_str("STOP_LOSS_LIMIT"),
+            TakeProfit => f.write_str("TAKE_PROFIT"),
             TakeProfitLimit => f.write_str("TAKE_PROFIT_LIMIT"),
         }
     }
@@ -107,6 +210,17 @@ pub enum Filter {
     MinNotional { min_notional: d128 },
 }
 
+impl Filter {
+    /// The filter's `filterType`, as Binance names it in its rejections.
+    fn name(&self) -> &'static str {
+        match *self {
+            Filter::PriceFilter { .. } => "PRICE_FILTER",
+            Filter::LotSize { .. } => "LOT_SIZE",
+            Filter::MinNotional { .. } => "MIN_NOTIONAL",
+        }
+    }
+}
+
 /// Interval of time. Mostly used in [`RateLimit`].
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
 #[serde(rename_all = "UPPERCASE")]
@@ -124,6 +238,10 @@ pub enum RateLimit {
 
     #[serde(rename_all = "camelCase")]
     Orders { interval: Interval, limit: u32 },
+
+    /// Total weight of requests allowed, where each endpoint has its own weight.
+    #[serde(rename_all = "camelCase")]
+    RequestWeight { interval: Interval, limit: u32 },
 }
 
 /// Account balances, priviliges, fee rates, etc.
@@ -166,20 +284,115 @@ pub struct Balance {
     pub locked: d128,
 }
 
+impl Account {
+    /// What the API key used to get this account is allowed to do.
+    pub fn permissions(&self) -> Permissions {
+        Permissions {
+            can_read: true,
+            can_trade: self.can_trade,
+            can_withdraw: self.can_withdraw,
+        }
+    }
+
+    /// Balances that aren't entirely zero. Binance lists a balance for every asset it supports,
+    /// so most of `balances` is usually empty.
+    pub fn nonzero_balances(&self) -> Vec<&Balance> {
+        self.balances
+            .iter()
+            .filter(|balance| !balance.total().is_zero())
+            .collect()
+    }
+}
+
+impl Balance {
+    /// Free and locked funds combined.
+    pub fn total(&self) -> d128 {
+        self.free + self.locked
+    }
+}
+
 /// Market depth.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Orderbook {
     pub last_update_id: u64,
-    /// Vector of `(price, quantity, /*ignore this*/)`
-    pub asks: Vec<(d128, d128, [(); 0])>,
+    /// Vector of `(price, quantity)`
+    #[serde(deserialize_with = "deserialize_levels")]
+    pub asks: Vec<(d128, d128)>,
+
+    /// Vector of `(price, quantity)`
+    #[serde(deserialize_with = "deserialize_levels")]
+    pub bids: Vec<(d128, d128)>,
+}
+
+/// An order and how much of it has been executed.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Order {
+    /// This is `base` and `quote` concatenated. See [`ProductInfo::symbol`].
+    pub symbol: String,
+    pub order_id: u64,
+    pub client_order_id: String,
+    pub price: d128,
+    #[serde(rename = "origQty")]
+    pub original_quantity: d128,
+    #[serde(rename = "executedQty")]
+    pub executed_quantity: d128,
+    pub status: OrderStatus,
+    pub time_in_force: TimeInForce,
+    #[serde(rename = "type")]
+    pub instruction: OrderInstruction,
+    pub side: Side,
+    pub stop_price: Option<d128>,
+    #[serde(rename = "icebergQty")]
+    pub iceberg_quantity: Option<d128>,
+
+    /// When the order was created, in milliseconds. Only present when querying existing orders.
+    pub time: Option<u64>,
+
+    /// When the order was placed, in milliseconds. Only present in the response to placing an
+    /// order.
+    pub transact_time: Option<u64>,
+    pub is_working: Option<bool>,
+
+    /// Trades that filled the order as soon as it was placed. Only present in the response to
+    /// placing an order; always empty when querying existing orders.
+    #[serde(default)]
+    pub fills: Vec<Fill>,
+}
 
-    /// Vector of `(price, quantity, /*ignore this*/)`
-    pub bids: Vec<(d128, d128, [(); 0])>,
+impl Order {
+    /// Quantity that hasn't been executed yet.
+    pub fn remaining_quantity(&self) -> d128 {
+        self.original_quantity - self.executed_quantity
+    }
 }
 
+/// A trade that filled part of an order.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
-pub struct Order {}
+#[serde(rename_all = "camelCase")]
+pub struct Fill {
+    pub trade_id: u64,
+    pub price: d128,
+    #[serde(rename = "qty")]
+    pub quantity: d128,
+    pub commission: d128,
+    #[serde(rename = "commissionAsset")]
+    pub commission_currency: Currency,
+}
+
+/// Status of an order.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OrderStatus {
+    New,
+    PartiallyFilled,
+    Filled,
+    Canceled,
+    PendingCancel,
+    Rejected,
+    Expired,
+}
 
 /// Result of a `cancel_order` request.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
@@ -191,6 +404,163 @@ pub struct OrderCancellation {
     pub client_order_id: String,
 }
 
+/// What `cancel_replace_order` does if cancelling the old order fails.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum CancelReplaceMode {
+    /// Don't place the new order.
+    StopOnFailure,
+
+    /// Place the new order anyway.
+    AllowFailure,
+}
+
+impl Display for CancelReplaceMode {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            CancelReplaceMode::StopOnFailure => f.write_str("STOP_ON_FAILURE"),
+            CancelReplaceMode::AllowFailure => f.write_str("ALLOW_FAILURE"),
+        }
+    }
+}
+
+/// Whether one half of a cancel-replace went through.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OperationResult {
+    Success,
+    Failure,
+    NotAttempted,
+}
+
+/// Either what Binance returned for a request, or the error it returned instead.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+#[serde(untagged)]
+pub enum Outcome<T> {
+    Done(T),
+    Failed { code: i64, msg: String },
+}
+
+/// Result of a `cancel_replace_order` request. Check both halves; the cancel can succeed while the
+/// new order fails, or the other way around with `CancelReplaceMode::AllowFailure`.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelReplaceResult {
+    pub cancel_result: OperationResult,
+    pub new_order_result: OperationResult,
+    pub cancel_response: Outcome<OrderCancellation>,
+
+    /// `None` if the new order wasn't attempted.
+    pub new_order_response: Option<Outcome<Order>>,
+}
+
+/// A one-cancels-the-other order list: two orders on one product where filling either cancels
+/// the other.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OcoOrder {
+    pub order_list_id: u64,
+
+    /// Always `"OCO"`.
+    pub contingency_type: String,
+    pub list_status_type: ListStatusType,
+    pub list_order_status: ListOrderStatus,
+    pub list_client_order_id: String,
+
+    /// When the list was last changed, in milliseconds.
+    pub transaction_time: u64,
+
+    /// This is `base` and `quote` concatenated. See [`ProductInfo::symbol`].
+    pub symbol: String,
+
+    /// The list's orders. Use `get_order` for their status.
+    pub orders: Vec<OcoOrderId>,
+}
+
+/// Identifies one of an [`OcoOrder`]'s orders.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OcoOrderId {
+    pub symbol: String,
+    pub order_id: u64,
+    pub client_order_id: String,
+}
+
+/// Status of an order list as a whole.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ListStatusType {
+    /// The list failed or was cancelled.
+    Response,
+    ExecStarted,
+    AllDone,
+}
+
+/// Status of an order list's orders.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ListOrderStatus {
+    Executing,
+    AllDone,
+    Reject,
+}
+
+/// A past trade on a product.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Trade {
+    pub id: u64,
+    pub price: d128,
+    #[serde(rename = "qty")]
+    pub quantity: d128,
+
+    /// When the trade happened, in milliseconds.
+    pub time: u64,
+    pub is_buyer_maker: bool,
+    pub is_best_match: bool,
+}
+
+/// Result of a `withdraw` request.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+struct Withdrawal {
+    id: String,
+}
+
+/// Result of a `dust_transfer` request.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DustResult {
+    /// Fee paid for the conversion, in BNB.
+    pub total_service_charge: d128,
+
+    /// BNB received, after fees.
+    pub total_transfered: d128,
+
+    /// One entry per converted asset. An asset Binance couldn't convert is left out rather than
+    /// failing the whole transfer, so compare this with the assets requested.
+    pub transfer_result: Vec<DustTransfer>,
+}
+
+/// Conversion of one asset's balance into BNB.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DustTransfer {
+    pub tran_id: u64,
+    pub from_asset: Currency,
+
+    /// Amount of `from_asset` converted.
+    pub amount: d128,
+
+    /// Fee paid for converting this asset, in BNB.
+    pub service_charge_amount: d128,
+
+    /// BNB received for this asset, after fees.
+    pub transfered_amount: d128,
+
+    /// When the conversion happened, in milliseconds.
+    pub operate_time: u64,
+}
+
 /// `Buy` or `Sell`
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
 #[serde(rename_all = "UPPERCASE")]
@@ -208,6 +578,16 @@ impl Display for Side {
     }
 }
 
+/// How much to buy or sell with a market order.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub enum MarketOrderSize {
+    /// Quantity of the base currency, e.g. "buy 1 BTC".
+    Quantity(d128),
+
+    /// Amount of the quote currency to spend or receive, e.g. "buy 100 USDT of BTC".
+    QuoteQuantity(d128),
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
 pub enum TimeInForce {
     #[serde(rename = "IOC")]
@@ -242,17 +622,20 @@ pub struct Currency(String);
 impl FromStr for Currency {
     type Err = Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Currency(s.to_uppercase()))
+        Ok(Currency(format_currency(s, CURRENCY_CASE)))
     }
 }
 
 impl Display for Currency {
     fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
         let &Currency(ref currency) = self;
-        f.write_str(currency.as_str())
+        f.write_str(&format_currency(currency, CURRENCY_CASE))
     }
 }
 
+/// Binance only accepts currencies like `BTC`.
+const CURRENCY_CASE: Case = Case::Upper;
+
 /// Usually represents a product. `ETH_BTC`, `BTC_USDT`, etc.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
 pub struct CurrencyPair(pub Currency, pub Currency);
@@ -279,10 +662,247 @@ impl Display for CurrencyPair {
     }
 }
 
+/// The most common currencies Binance lists products against. Symbols have no separator, so this
+/// is how a symbol like `ETHBTC` is split when there's no [`ExchangeInfo`] to look it up in.
+const QUOTE_CURRENCIES: &[&str] = &[
+    "USDT", "BUSD", "USDC", "TUSD", "PAX", "BTC", "ETH", "BNB", "XRP", "TRX",
+];
+
+impl FromStr for CurrencyPair {
+    type Err = Error;
+
+    /// Parse a symbol like `ETHBTC` by matching the longest common quote currency at its end.
+    ///
+    /// Products quoted in anything else can't be split this way; use
+    /// [`ExchangeInfo::currency_pair`] for those.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let symbol = s.to_uppercase();
+        let quote = QUOTE_CURRENCIES
+            .iter()
+            .filter(|quote| symbol.len() > quote.len() && symbol.ends_with(*quote))
+            .max_by_key(|quote| quote.len())
+            .ok_or_else(|| {
+                format_err!(
+                    "can't split {:?}: it doesn't end in a common quote currency; look it up with \
+                     ExchangeInfo::currency_pair",
+                    s
+                )
+            })?;
+        let (base, quote) = symbol.split_at(symbol.len() - quote.len());
+        Ok(CurrencyPair(base.parse()?, quote.parse()?))
+    }
+}
+
+/// Kind of public websocket stream for a single product.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub enum StreamKind {
+    /// Orderbook updates; see [`DepthUpdate`].
+    Depth,
+    /// Individual trades; see [`TradeEvent`].
+    Trade,
+}
+
+impl Display for StreamKind {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            StreamKind::Depth => f.write_str("depth"),
+            StreamKind::Trade => f.write_str("trade"),
+        }
+    }
+}
+
+/// A message received from a combined stream. `stream` is the name of the stream the event came
+/// from, e.g. `"btcusdt@depth"`.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+pub struct StreamMessage {
+    pub stream: String,
+    pub data: StreamEvent,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+#[serde(tag = "e")]
+pub enum StreamEvent {
+    #[serde(rename = "depthUpdate")]
+    DepthUpdate(DepthUpdate),
+    #[serde(rename = "trade")]
+    Trade(TradeEvent),
+}
+
+/// Changes to a product's orderbook. A quantity of `0` means the price level was removed.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+pub struct DepthUpdate {
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "U")]
+    pub first_update_id: u64,
+    #[serde(rename = "u")]
+    pub final_update_id: u64,
+
+    /// Vector of `(price, quantity)`
+    #[serde(rename = "b", deserialize_with = "deserialize_levels")]
+    pub bids: Vec<(d128, d128)>,
+
+    /// Vector of `(price, quantity)`
+    #[serde(rename = "a", deserialize_with = "deserialize_levels")]
+    pub asks: Vec<(d128, d128)>,
+}
+
+impl DepthUpdate {
+    /// Whether this update directly follows the update (or [`Orderbook`] snapshot) whose last
+    /// update id is `last_update_id`. If it doesn't, updates were missed and the orderbook needs
+    /// to be fetched again.
+    ///
+    /// Updates older than a snapshot should be dropped rather than treated as a gap; check for
+    /// `final_update_id <= last_update_id` first.
+    pub fn follows(&self, last_update_id: u64) -> bool {
+        self.first_update_id <= last_update_id + 1 && self.final_update_id >= last_update_id + 1
+    }
+}
+
+/// A single trade between a buyer and a seller.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+pub struct TradeEvent {
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "t")]
+    pub trade_id: u64,
+    #[serde(rename = "p")]
+    pub price: d128,
+    #[serde(rename = "q")]
+    pub quantity: d128,
+    #[serde(rename = "b")]
+    pub buyer_order_id: u64,
+    #[serde(rename = "a")]
+    pub seller_order_id: u64,
+    #[serde(rename = "T")]
+    pub trade_time: u64,
+    #[serde(rename = "m")]
+    pub is_buyer_maker: bool,
+}
+
+/// Wraps an `HttpClient`, keeping track of the request weight Binance says has been used in the
+/// current minute so callers can slow down before getting banned.
+///
+/// ```rust,ignore
+/// let exchange_info = binance::get_exchange_info(&mut client, &host)?;
+/// let mut client = WeightTracking::new(client, &exchange_info).sleep_below(50);
+/// ```
+#[derive(Debug)]
+pub struct WeightTracking<Client> {
+    pub client: Client,
+    limit: u32,
+    used: u32,
+
+    /// The minute `used` was reported in, counted from the epoch.
+    minute: u64,
+    sleep_below: Option<u32>,
+}
+
+impl<Client> WeightTracking<Client>
+where Client: HttpClient
+{
+    /// Binance's per-minute weight limit at the time of writing.
+    const DEFAULT_LIMIT: u32 = 1200;
+
+    /// Track weight against the per-minute limit in `exchange_info`.
+    pub fn new(client: Client, exchange_info: &ExchangeInfo) -> Self {
+        let limit = exchange_info
+            .rate_limits
+            .iter()
+            .filter_map(|rate_limit| match *rate_limit {
+                RateLimit::RequestWeight { interval: Interval::Minute, limit }
+                | RateLimit::Requests { interval: Interval::Minute, limit } => Some(limit),
+                _ => None,
+            })
+            .min()
+            .unwrap_or(Self::DEFAULT_LIMIT);
+        WeightTracking {
+            client,
+            limit,
+            used: 0,
+            minute: 0,
+            sleep_below: None,
+        }
+    }
+
+    /// Before sending a request, sleep until the next minute if less than `weight` remains.
+    pub fn sleep_below(mut self, weight: u32) -> Self {
+        self.sleep_below = Some(weight);
+        self
+    }
+
+    /// Weight that can still be used this minute.
+    pub fn remaining_weight(&self) -> u32 {
+        if self.minute == now_millis() / 60_000 {
+            self.limit.saturating_sub(self.used)
+        } else {
+            self.limit
+        }
+    }
+}
+
+impl<Client> HttpClient for WeightTracking<Client>
+where Client: HttpClient
+{
+    fn send(&mut self, request: &http::Request<String>) -> Result<http::Response<String>, Error> {
+        if let Some(threshold) = self.sleep_below {
+            if self.remaining_weight() < threshold {
+                let until_next_minute = 60_000 - now_millis() % 60_000;
+                thread::sleep(Duration::from_millis(until_next_minute));
+            }
+        }
+
+        let response = self.client.send(request)?;
+
+        let used = ["X-MBX-USED-WEIGHT-1M", "X-MBX-USED-WEIGHT"]
+            .iter()
+            .filter_map(|name| response.headers().get(*name))
+            .filter_map(|value| value.to_str().ok())
+            .filter_map(|value| value.parse().ok())
+            .next();
+        if let Some(used) = used {
+            self.used = used;
+            self.minute = now_millis() / 60_000;
+        }
+
+        Ok(response)
+    }
+}
+
+/// Address of a combined stream delivering every `(product, kind)` in `streams`. Messages
+/// received from it are [`StreamMessage`]s.
+///
+/// ```rust
+/// # extern crate failure;
+/// # extern crate ni_ce;
+/// # use ni_ce::binance::{stream_url, CurrencyPair, StreamKind, STREAM_HOST};
+/// # fn main() -> Result<(), failure::Error> {
+/// let btc_usdt: CurrencyPair = "BTCUSDT".parse()?;
+/// let streams = [
+///     (btc_usdt.clone(), StreamKind::Depth),
+///     (btc_usdt, StreamKind::Trade),
+/// ];
+/// let url = stream_url(STREAM_HOST, &streams);
+/// assert_eq!(url, "wss://stream.binance.com:9443/stream?streams=btcusdt@depth/btcusdt@trade");
+/// # Ok(())
+/// # }
+/// ```
+pub fn stream_url(host: &str, streams: &[(CurrencyPair, StreamKind)]) -> String {
+    let streams: Vec<String> = streams
+        .iter()
+        .map(|&(ref product, kind)| format!("{}@{}", product.to_string().to_lowercase(), kind))
+        .collect();
+    format!("{}/stream?streams={}", host, streams.join("/"))
+}
+
 /// **Private**. Get priviliges, commission rates, and balances for an account.
 pub fn get_account_info<Client>(
     client: &mut Client,
-    host: &str,
+    host: &Host,
     credential: &Credential,
 ) -> Result<Account, Error>
 where
@@ -307,7 +927,7 @@ where
 }
 
 /// **Public**.
-pub fn get_exchange_info<Client>(client: &mut Client, host: &str) -> Result<ExchangeInfo, Error>
+pub fn get_exchange_info<Client>(client: &mut Client, host: &Host) -> Result<ExchangeInfo, Error>
 where Client: HttpClient {
     let http_request = http::request::Builder::new()
         .method(http::Method::GET)
@@ -319,10 +939,73 @@ where Client: HttpClient {
     deserialize_public_response(&http_response)
 }
 
+/// **Public**. Binance's current time.
+pub fn get_server_time<Client>(client: &mut Client, host: &Host) -> Result<DateTime<Utc>, Error>
+where Client: HttpClient {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct ServerTime {
+        server_time: i64,
+    }
+
+    let http_request = http::request::Builder::new()
+        .method(http::Method::GET)
+        .uri(format!("{}/api/v1/time", host))
+        .body(String::new())?;
+
+    let http_response = client.send(&http_request)?;
+
+    let time: ServerTime = deserialize_public_response(&http_response)?;
+    let seconds = time.server_time / 1000;
+    let nanoseconds = (time.server_time % 1000) as u32 * 1_000_000;
+    Ok(DateTime::from_utc(NaiveDateTime::from_timestamp(seconds, nanoseconds), Utc))
+}
+
+/// **Public**. How far Binance's clock is ahead of the local clock; negative if it's behind.
+///
+/// Private requests are rejected if their timestamp is too far from Binance's time, so check this
+/// when they fail with a timestamp error.
+pub fn clock_skew<Client>(client: &mut Client, host: &Host) -> Result<chrono::Duration, Error>
+where Client: HttpClient {
+    util::clock_skew(|| get_server_time(client, host))
+}
+
+/// **Public**. Filters, precision, and order types for a single product. Much smaller than
+/// `get_exchange_info` when only a few products are traded.
+pub fn get_symbol_info<Client>(
+    client: &mut Client,
+    host: &Host,
+    product: &CurrencyPair,
+) -> Result<ProductInfo, Error>
+where
+    Client: HttpClient,
+{
+    let query = {
+        let mut query = Query::with_capacity(1);
+        query.append_param("symbol", product.to_string());
+        query.to_string()
+    };
+    let http_request = http::request::Builder::new()
+        .method(http::Method::GET)
+        .uri(format!("{}/api/v3/exchangeInfo?{}", host, query))
+        .body(String::new())?;
+
+    let http_response = client.send(&http_request)?;
+
+    // Match the symbol rather than taking the first product, in case Binance ignores `symbol`.
+    let symbol = product.to_string();
+    let exchange_info: ExchangeInfo = deserialize_public_response(&http_response)?;
+    exchange_info
+        .products
+        .into_iter()
+        .find(|product_info| product_info.symbol == symbol)
+        .ok_or_else(|| format_err!("{} isn't listed in the exchange info", product))
+}
+
 /// **Public**. Get the orderbook for a single product.
 pub fn get_orderbook<Client>(
     client: &mut Client,
-    host: &str,
+    host: &Host,
     product: &CurrencyPair,
 ) -> Result<Orderbook, Error>
 where
@@ -344,29 +1027,78 @@ where
     deserialize_public_response(&http_response)
 }
 
+/// **API key**. Get older trades for a product, starting at `from_id` or the most recent trades
+/// if it's `None`. `limit` defaults to `500` and is capped at `1000` by Binance.
+///
+/// Only the API key is needed; the request isn't signed, so the secret never leaves the machine.
+pub fn get_historical_trades<Client>(
+    client: &mut Client,
+    host: &Host,
+    credential_key: &str,
+    product: &CurrencyPair,
+    from_id: Option<u64>,
+    limit: Option<u16>,
+) -> Result<Vec<Trade>, Error>
+where
+    Client: HttpClient,
+{
+    let query = {
+        let mut query = Query::with_capacity(3);
+        query.append_param("symbol", product.to_string());
+        if let Some(from_id) = from_id {
+            query.append_param("fromId", from_id.to_string());
+        }
+        if let Some(limit) = limit {
+            query.append_param("limit", limit.to_string());
+        }
+        query.to_string()
+    };
+    let http_request = http::request::Builder::new()
+        .method(http::Method::GET)
+        .uri(format!("{}/api/v1/historicalTrades?{}", host, query))
+        .header(X_MBX_APIKEY, credential_key)
+        .body(String::new())?;
+
+    let http_response = client.send(&http_request)?;
+
+    deserialize_private_response(&http_response)
+}
+
 /// **Private**. Place a limit order.
+///
+/// `client_order_id` is an id of your choosing for the order. If a placement times out, placing
+/// it again with the same id can't create a second order; Binance rejects the duplicate. If it's
+/// `None`, Binance generates one.
+///
+/// Any part of the order that executed immediately is listed in the returned order's `fills`.
 pub fn place_limit_order<Client>(
     client: &mut Client,
-    host: &str,
+    host: &Host,
     credential: &Credential,
     product: &CurrencyPair,
     price: d128,
     quantity: d128,
     time_in_force: TimeInForce,
     side: Side,
+    client_order_id: Option<&str>,
 ) -> Result<Order, Error>
 where
     Client: HttpClient,
 {
     let query = {
-        let mut query = Query::with_capacity(7);
+        let mut query = Query::with_capacity(10);
         query.append_param("timestamp", timestamp_now().to_string());
         query.append_param("symbol", product.to_string());
         query.append_param("side", side.to_string());
         query.append_param("type", OrderInstruction::Limit.to_string());
-        query.append_param("quantity", quantity.to_string());
-        query.append_param("price", price.to_string());
+        let price_rounding = RoundingMode::for_price(side == Side::Buy);
+        query.append_param("quantity", format_amount(quantity, PRECISION, RoundingMode::Down));
+        query.append_param("price", format_amount(price, PRECISION, price_rounding));
         query.append_param("timeInForce", time_in_force.to_string());
+        query.append_param("newOrderRespType", "FULL");
+        if let Some(client_order_id) = client_order_id {
+            query.append_param("newClientOrderId", client_order_id);
+        }
         let signature = private_signature(credential, query.to_string().as_str())?;
         query.append_param("signature", signature);
         query.to_string()
@@ -382,12 +1114,147 @@ where
     deserialize_private_response(&http_response)
 }
 
-/// **Private**. Cancel an active order by Binance-issued order id.
-pub fn cancel_order<Client>(
+/// **Private**. Place a limit order after rounding it to the product's precision and validating
+/// it against the product's filters.
+///
+/// An order that would be rejected for breaking a filter is never sent. See
+/// [`ProductInfo::validate_order`].
+pub fn place_validated_limit_order<Client>(
     client: &mut Client,
-    host: &str,
+    host: &Host,
     credential: &Credential,
-    order_id: u64,
+    product_info: &ProductInfo,
+    product: &CurrencyPair,
+    price: d128,
+    quantity: d128,
+    time_in_force: TimeInForce,
+    side: Side,
+    client_order_id: Option<&str>,
+) -> Result<Order, Error>
+where
+    Client: HttpClient,
+{
+    let price = product_info.round_price(price, RoundingMode::for_price(side == Side::Buy));
+    let quantity = product_info.round_quantity(quantity, RoundingMode::Down);
+    product_info.validate_order(price, quantity)?;
+    place_limit_order(
+        client,
+        host,
+        credential,
+        product,
+        price,
+        quantity,
+        time_in_force,
+        side,
+        client_order_id,
+    )
+}
+
+/// **Private**. Place a market order.
+///
+/// See [`place_limit_order`] for `client_order_id`.
+pub fn place_market_order<Client>(
+    client: &mut Client,
+    host: &Host,
+    credential: &Credential,
+    product: &CurrencyPair,
+    size: MarketOrderSize,
+    side: Side,
+    client_order_id: Option<&str>,
+) -> Result<Order, Error>
+where
+    Client: HttpClient,
+{
+    let query = {
+        let mut query = Query::with_capacity(8);
+        query.append_param("timestamp", timestamp_now().to_string());
+        query.append_param("symbol", product.to_string());
+        query.append_param("side", side.to_string());
+        query.append_param("type", OrderInstruction::Market.to_string());
+        match size {
+            MarketOrderSize::Quantity(quantity) => {
+                let quantity = format_amount(quantity, PRECISION, RoundingMode::Down);
+                query.append_param("quantity", quantity);
+            }
+            MarketOrderSize::QuoteQuantity(quote_quantity) => {
+                let quote_quantity = format_amount(quote_quantity, PRECISION, RoundingMode::Down);
+                query.append_param("quoteOrderQty", quote_quantity);
+            }
+        }
+        query.append_param("newOrderRespType", "FULL");
+        if let Some(client_order_id) = client_order_id {
+            query.append_param("newClientOrderId", client_order_id);
+        }
+        let signature = private_signature(credential, query.to_string().as_str())?;
+        query.append_param("signature", signature);
+        query.to_string()
+    };
+    let http_request = http::request::Builder::new()
+        .method(http::Method::POST)
+        .uri(format!("{}/api/v3/order?{}", host, query))
+        .header(X_MBX_APIKEY, credential.key.as_str())
+        .body(String::new())?;
+
+    let http_response = client.send(&http_request)?;
+
+    deserialize_private_response(&http_response)
+}
+
+/// **Private**. Check a limit order without placing it.
+///
+/// Binance validates the order against the product's filters and checks the signature, then
+/// discards it. Returns `Ok(())` if the order would have been accepted.
+pub fn test_order<Client>(
+    client: &mut Client,
+    host: &Host,
+    credential: &Credential,
+    product: &CurrencyPair,
+    price: d128,
+    quantity: d128,
+    time_in_force: TimeInForce,
+    side: Side,
+    client_order_id: Option<&str>,
+) -> Result<(), Error>
+where
+    Client: HttpClient,
+{
+    let query = {
+        let mut query = Query::with_capacity(9);
+        query.append_param("timestamp", timestamp_now().to_string());
+        query.append_param("symbol", product.to_string());
+        query.append_param("side", side.to_string());
+        query.append_param("type", OrderInstruction::Limit.to_string());
+        let price_rounding = RoundingMode::for_price(side == Side::Buy);
+        query.append_param("quantity", format_amount(quantity, PRECISION, RoundingMode::Down));
+        query.append_param("price", format_amount(price, PRECISION, price_rounding));
+        query.append_param("timeInForce", time_in_force.to_string());
+        if let Some(client_order_id) = client_order_id {
+            query.append_param("newClientOrderId", client_order_id);
+        }
+        let signature = private_signature(credential, query.to_string().as_str())?;
+        query.append_param("signature", signature);
+        query.to_string()
+    };
+    let http_request = http::request::Builder::new()
+        .method(http::Method::POST)
+        .uri(format!("{}/api/v3/order/test?{}", host, query))
+        .header(X_MBX_APIKEY, credential.key.as_str())
+        .body(String::new())?;
+
+    let http_response = client.send(&http_request)?;
+
+    // Success is an empty object.
+    let _: serde_json::Map<String, serde_json::Value> =
+        deserialize_private_response(&http_response)?;
+    Ok(())
+}
+
+/// **Private**. Cancel an active order by Binance-issued order id.
+pub fn cancel_order<Client>(
+    client: &mut Client,
+    host: &Host,
+    credential: &Credential,
+    order_id: u64,
     product: &CurrencyPair,
 ) -> Result<OrderCancellation, Error>
 where
@@ -413,10 +1280,138 @@ where
     deserialize_private_response(&http_response)
 }
 
+/// **Private**. Cancel an order and place a new limit order in its place, in one request.
+///
+/// Unlike `cancel_order` followed by `place_limit_order`, there's no gap between the two in which
+/// the account has no order. Failures of either half are reported in the result rather than as an
+/// error, so a cancel that succeeded isn't hidden by a placement that didn't.
+pub fn cancel_replace_order<Client>(
+    client: &mut Client,
+    host: &Host,
+    credential: &Credential,
+    product: &CurrencyPair,
+    cancel_order_id: u64,
+    price: d128,
+    quantity: d128,
+    time_in_force: TimeInForce,
+    side: Side,
+    client_order_id: Option<&str>,
+    mode: CancelReplaceMode,
+) -> Result<CancelReplaceResult, Error>
+where
+    Client: HttpClient,
+{
+    let query = {
+        let mut query = Query::with_capacity(12);
+        query.append_param("timestamp", timestamp_now().to_string());
+        query.append_param("symbol", product.to_string());
+        query.append_param("cancelReplaceMode", mode.to_string());
+        query.append_param("cancelOrderId", cancel_order_id.to_string());
+        query.append_param("side", side.to_string());
+        query.append_param("type", OrderInstruction::Limit.to_string());
+        let price_rounding = RoundingMode::for_price(side == Side::Buy);
+        query.append_param("quantity", format_amount(quantity, PRECISION, RoundingMode::Down));
+        query.append_param("price", format_amount(price, PRECISION, price_rounding));
+        query.append_param("timeInForce", time_in_force.to_string());
+        query.append_param("newOrderRespType", "FULL");
+        if let Some(client_order_id) = client_order_id {
+            query.append_param("newClientOrderId", client_order_id);
+        }
+        let signature = private_signature(credential, query.to_string().as_str())?;
+        query.append_param("signature", signature);
+        query.to_string()
+    };
+    let http_request = http::request::Builder::new()
+        .method(http::Method::POST)
+        .uri(format!("{}/api/v3/order/cancelReplace?{}", host, query))
+        .header(X_MBX_APIKEY, credential.key.as_str())
+        .body(String::new())?;
+
+    let http_response = client.send(&http_request)?;
+
+    // When either half fails, Binance responds with an error status but still describes both
+    // halves in `data`.
+    #[derive(Deserialize)]
+    struct FailedCancelReplace {
+        data: CancelReplaceResult,
+    }
+    if !http_response.status().is_success() {
+        if let Ok(failed) = serde_json::from_str::<FailedCancelReplace>(http_response.body()) {
+            return Ok(failed.data);
+        }
+    }
+
+    deserialize_private_response(&http_response)
+}
+
+/// **Private**. Get a single order by Binance-issued order id.
+pub fn get_order<Client>(
+    client: &mut Client,
+    host: &Host,
+    credential: &Credential,
+    order_id: u64,
+    product: &CurrencyPair,
+) -> Result<Order, Error>
+where
+    Client: HttpClient,
+{
+    let query = {
+        let mut query = Query::with_capacity(4);
+        query.append_param("timestamp", timestamp_now().to_string());
+        query.append_param("symbol", product.to_string());
+        query.append_param("orderId", order_id.to_string());
+        let signature = private_signature(credential, query.to_string().as_str())?;
+        query.append_param("signature", signature);
+        query.to_string()
+    };
+    let http_request = http::request::Builder::new()
+        .method(http::Method::GET)
+        .uri(format!("{}/api/v3/order?{}", host, query))
+        .header(X_MBX_APIKEY, credential.key.as_str())
+        .body(String::new())?;
+
+    let http_response = client.send(&http_request)?;
+
+    deserialize_private_response(&http_response)
+}
+
+/// **Private**. Get a single order by the `client_order_id` it was placed with.
+///
+/// Use this to find out whether a placement that timed out went through.
+pub fn get_order_by_client_order_id<Client>(
+    client: &mut Client,
+    host: &Host,
+    credential: &Credential,
+    client_order_id: &str,
+    product: &CurrencyPair,
+) -> Result<Order, Error>
+where
+    Client: HttpClient,
+{
+    let query = {
+        let mut query = Query::with_capacity(4);
+        query.append_param("timestamp", timestamp_now().to_string());
+        query.append_param("symbol", product.to_string());
+        query.append_param("origClientOrderId", client_order_id);
+        let signature = private_signature(credential, query.to_string().as_str())?;
+        query.append_param("signature", signature);
+        query.to_string()
+    };
+    let http_request = http::request::Builder::new()
+        .method(http::Method::GET)
+        .uri(format!("{}/api/v3/order?{}", host, query))
+        .header(X_MBX_APIKEY, credential.key.as_str())
+        .body(String::new())?;
+
+    let http_response = client.send(&http_request)?;
+
+    deserialize_private_response(&http_response)
+}
+
 /// **Private**. Get all open orders for every product or all open orders for one product.
 pub fn get_open_orders<Client>(
     client: &mut Client,
-    host: &str,
+    host: &Host,
     credential: &Credential,
     product: Option<CurrencyPair>,
 ) -> Result<Vec<Order>, Error>
@@ -444,21 +1439,226 @@ where
     deserialize_private_response(&http_response)
 }
 
+/// **Private**. Get all open OCO order lists, for every product.
+pub fn get_open_oco_orders<Client>(
+    client: &mut Client,
+    host: &Host,
+    credential: &Credential,
+) -> Result<Vec<OcoOrder>, Error>
+where
+    Client: HttpClient,
+{
+    let query = {
+        let mut query = Query::with_capacity(2);
+        query.append_param("timestamp", timestamp_now().to_string());
+        let signature = private_signature(credential, query.to_string().as_str())?;
+        query.append_param("signature", signature);
+        query.to_string()
+    };
+    let http_request = http::request::Builder::new()
+        .method(http::Method::GET)
+        .uri(format!("{}/api/v3/openOrderList?{}", host, query))
+        .header(X_MBX_APIKEY, credential.key.as_str())
+        .body(String::new())?;
+
+    let http_response = client.send(&http_request)?;
+
+    deserialize_private_response(&http_response)
+}
+
+/// **Private**. Get a single OCO order list, open or not.
+pub fn get_oco_order<Client>(
+    client: &mut Client,
+    host: &Host,
+    credential: &Credential,
+    order_list_id: u64,
+) -> Result<OcoOrder, Error>
+where
+    Client: HttpClient,
+{
+    let query = {
+        let mut query = Query::with_capacity(3);
+        query.append_param("timestamp", timestamp_now().to_string());
+        query.append_param("orderListId", order_list_id.to_string());
+        let signature = private_signature(credential, query.to_string().as_str())?;
+        query.append_param("signature", signature);
+        query.to_string()
+    };
+    let http_request = http::request::Builder::new()
+        .method(http::Method::GET)
+        .uri(format!("{}/api/v3/orderList?{}", host, query))
+        .header(X_MBX_APIKEY, credential.key.as_str())
+        .body(String::new())?;
+
+    let http_response = client.send(&http_request)?;
+
+    deserialize_private_response(&http_response)
+}
+
+/// **Private**. Cancel an OCO order list, and so both of its orders.
+pub fn cancel_oco_order<Client>(
+    client: &mut Client,
+    host: &Host,
+    credential: &Credential,
+    order_list_id: u64,
+    product: &CurrencyPair,
+) -> Result<OcoOrder, Error>
+where
+    Client: HttpClient,
+{
+    let query = {
+        let mut query = Query::with_capacity(4);
+        query.append_param("timestamp", timestamp_now().to_string());
+        query.append_param("symbol", product.to_string());
+        query.append_param("orderListId", order_list_id.to_string());
+        let signature = private_signature(credential, query.to_string().as_str())?;
+        query.append_param("signature", signature);
+        query.to_string()
+    };
+    let http_request = http::request::Builder::new()
+        .method(http::Method::DELETE)
+        .uri(format!("{}/api/v3/orderList?{}", host, query))
+        .header(X_MBX_APIKEY, credential.key.as_str())
+        .body(String::new())?;
+
+    let http_response = client.send(&http_request)?;
+
+    deserialize_private_response(&http_response)
+}
+
+/// **Private**. Convert the small balances of `assets` into BNB.
+pub fn dust_transfer<Client>(
+    client: &mut Client,
+    host: &Host,
+    credential: &Credential,
+    assets: &[Currency],
+) -> Result<DustResult, Error>
+where
+    Client: HttpClient,
+{
+    if assets.is_empty() {
+        return Err(format_err!("at least one asset is needed for a dust transfer"));
+    }
+
+    let query = {
+        let mut query = Query::with_capacity(assets.len() + 2);
+        query.append_param("timestamp", timestamp_now().to_string());
+        for asset in assets {
+            query.append_param("asset", asset.to_string());
+        }
+        let signature = private_signature(credential, query.to_string().as_str())?;
+        query.append_param("signature", signature);
+        query.to_string()
+    };
+    let http_request = http::request::Builder::new()
+        .method(http::Method::POST)
+        .uri(format!("{}/sapi/v1/asset/dust?{}", host, query))
+        .header(X_MBX_APIKEY, credential.key.as_str())
+        .body(String::new())?;
+
+    let http_response = client.send(&http_request)?;
+
+    deserialize_private_response(&http_response)
+}
+
+/// **Private**. Withdraw `amount` of `currency` to `address`, returning the withdrawal id.
+///
+/// **Withdrawals can't be undone.** An `address` that isn't on `guard`'s allowlist is refused with
+/// `CcexError::AddressNotWhitelisted` before anything is sent. `network` picks the chain for
+/// currencies on more than one, e.g. `"BSC"`; `None` uses the currency's default network.
+///
+/// The API key needs withdrawals enabled. Check with [`Account::permissions`].
+pub fn withdraw<Client>(
+    client: &mut Client,
+    host: &Host,
+    credential: &Credential,
+    guard: &WhitelistGuard,
+    currency: &Currency,
+    amount: d128,
+    address: &str,
+    network: Option<&str>,
+) -> Result<String, Error>
+where
+    Client: HttpClient,
+{
+    guard.check(address)?;
+    withdraw_unguarded(client, host, credential, currency, amount, address, network)
+}
+
+/// **Private**. Like [`withdraw`], but sends funds to `address` without checking it against an
+/// allowlist. Prefer [`withdraw`].
+pub fn withdraw_unguarded<Client>(
+    client: &mut Client,
+    host: &Host,
+    credential: &Credential,
+    currency: &Currency,
+    amount: d128,
+    address: &str,
+    network: Option<&str>,
+) -> Result<String, Error>
+where
+    Client: HttpClient,
+{
+    let query = {
+        let mut query = Query::with_capacity(6);
+        query.append_param("coin", currency.to_string());
+        query.append_param("address", address);
+        query.append_param("amount", format_amount(amount, PRECISION, RoundingMode::Down));
+        if let Some(network) = network {
+            query.append_param("network", network);
+        }
+        query.append_param("timestamp", timestamp_now().to_string());
+        let signature = private_signature(credential, query.to_string().as_str())?;
+        query.append_param("signature", signature);
+        query.to_string()
+    };
+    let http_request = http::request::Builder::new()
+        .method(http::Method::POST)
+        .uri(format!("{}/sapi/v1/capital/withdraw/apply?{}", host, query))
+        .header(X_MBX_APIKEY, credential.key.as_str())
+        .body(String::new())?;
+
+    let http_response = client.send(&http_request)?;
+
+    let withdrawal: Withdrawal = deserialize_private_response(&http_response)?;
+    Ok(withdrawal.id)
+}
+
 fn timestamp_now() -> u64 {
     let now = Utc::now();
     // now.timestamp() as u64 * 1000 + now.timestamp_subsec_millis() as u64
     now.timestamp() as u64 * 1000
 }
 
+fn now_millis() -> u64 {
+    let now = Utc::now();
+    now.timestamp() as u64 * 1000 + u64::from(now.timestamp_subsec_millis())
+}
+
 fn private_signature(credential: &Credential, query: &str) -> Result<String, Error> {
-    let mut mac =
-        Hmac::<Sha256>::new(credential.secret.as_bytes()).map_err(|e| format_err!("{:?}", e))?;
-    mac.input(query.as_bytes());
-    Ok(hex::encode(mac.result().code().to_vec()))
+    HmacSha256Hex.sign(&credential.secret, query)
+}
+
+/// The query that's signed for a private request, its signature, and the query that's actually
+/// sent. Compare these against the documented example when a request is rejected for its
+/// signature.
+#[cfg(feature = "debug")]
+pub fn debug_signature(credential: &Credential, query: &str) -> Result<SignatureDebug, Error> {
+    let signature = private_signature(credential, query)?;
+    Ok(SignatureDebug {
+        signed: query.to_owned(),
+        query: Some(format!("{}&signature={}", query, signature)),
+        signature,
+    })
 }
 
 const X_MBX_APIKEY: &str = "X-MBX-APIKEY";
 
+/// Binance never accepts more than 8 decimal places for a price or quantity, and no product's
+/// precision is finer, so rounding to this only drops digits Binance would reject anyway.
+/// `place_validated_limit_order` rounds to the product's own precision first.
+const PRECISION: u32 = 8;
+
 fn deserialize_private_response<T>(response: &http::Response<String>) -> Result<T, Error>
 where T: DeserializeOwned {
     deserialize_public_response(response)
@@ -466,6 +1666,1008 @@ where T: DeserializeOwned {
 
 fn deserialize_public_response<T>(response: &http::Response<String>) -> Result<T, Error>
 where T: DeserializeOwned {
-    let result = serde_json::from_str(response.body().as_str())?;
+    let body = response.body();
+    if !response.status().is_success() {
+        return Err(classify_error(response).into());
+    }
+    util::require_json(response)?;
+    let result = serde_json::from_str(body.as_str())
+        .map_err(|e| CcexError::Deserialize(format!("{}: \"{}\"", e, body)))?;
     Ok(result)
 }
+
+/// Deserialize depth levels, `[["price", "quantity", []], ...]`, keeping only the price and
+/// quantity. Anything after them is skipped without being parsed, which matters on a 5000-level
+/// book.
+fn deserialize_levels<'de, D>(deserializer: D) -> Result<Vec<(d128, d128)>, D::Error>
+where D: Deserializer<'de> {
+    struct Level(d128, d128);
+    impl<'de> Deserialize<'de> for Level {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de> {
+            struct LevelVisitor;
+            impl<'de> Visitor<'de> for LevelVisitor {
+                type Value = Level;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str("an array starting with a price and a quantity")
+                }
+
+                fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+                where A: SeqAccess<'de> {
+                    let price = seq
+                        .next_element()?
+                        .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                    let quantity = seq
+                        .next_element()?
+                        .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+                    while let Some(IgnoredAny) = seq.next_element()? {}
+                    Ok(Level(price, quantity))
+                }
+            }
+            deserializer.deserialize_seq(LevelVisitor)
+        }
+    }
+
+    struct LevelsVisitor;
+    impl<'de> Visitor<'de> for LevelsVisitor {
+        type Value = Vec<(d128, d128)>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("an array of price levels")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where A: SeqAccess<'de> {
+            let mut levels = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(Level(price, quantity)) = seq.next_element()? {
+                levels.push((price, quantity));
+            }
+            Ok(levels)
+        }
+    }
+    deserializer.deserialize_seq(LevelsVisitor)
+}
+
+/// Error returned by Binance alongside a 4xx or 5xx status.
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    code: i64,
+    msg: String,
+}
+
+/// Sort a failed response by its status and Binance's error code.
+///
+/// Binance's error codes are listed
+/// [here](https://github.com/binance-exchange/binance-official-api-docs/blob/master/errors.md).
+fn classify_error(response: &http::Response<String>) -> CcexError {
+    let status = response.status().as_u16();
+    if status == 429 || status == 418 {
+        return CcexError::RateLimited { retry_after: util::retry_after(response) };
+    }
+
+    let body = response.body();
+    let error: ErrorResponse = match serde_json::from_str(body.as_str()) {
+        Ok(error) => error,
+        Err(_) => {
+            let message = format!("status {}: \"{}\"", status, util::snippet(body));
+            return CcexError::Venue { code: None, message };
+        }
+    };
+    if is_insufficient_funds(&error.msg) {
+        return CcexError::InsufficientFunds(error.msg);
+    }
+    match error.code {
+        -1003 => CcexError::RateLimited { retry_after: None },
+        -1022 | -2014 | -2015 => CcexError::Auth(error.msg),
+        -2010 | -1013 => CcexError::OrderRejected(error.msg),
+        code => CcexError::Venue { code: Some(code), message: error.msg },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {MockClient, MockResponse};
+
+    fn credential() -> Credential {
+        Credential {
+            key: "key".to_owned(),
+            secret: "secret".to_owned(),
+        }
+    }
+
+    #[test]
+    fn private_signature_is_hmac_sha256_of_the_query() {
+        let signature = private_signature(&credential(), "timestamp=1499827319559").unwrap();
+        assert_eq!(signature, "3ef9a17fb7e0bf61afddd2c62b55cee96ef6824c97fd10647c191c57e9728b6a");
+    }
+
+    #[test]
+    fn private_requests_sign_everything_before_the_signature() {
+        let body = r#"{"makerCommission":15,"takerCommission":15,"buyerCommission":0,
+            "sellerCommission":0,"canTrade":true,"canWithdraw":true,"canDeposit":true,
+            "updateTime":123456789,"balances":[]}"#;
+        let mut client = MockClient::new(body);
+
+        get_account_info(&mut client, &host(Environment::Production), &credential()).unwrap();
+
+        let request = &client.sent[0];
+        let query = request.uri().query().unwrap();
+        let (signed, signature) = query.split_at(query.find("&signature=").unwrap());
+        assert!(signed.starts_with("timestamp="));
+        let expected = private_signature(&credential(), signed).unwrap();
+        assert_eq!(&signature["&signature=".len()..], expected);
+        assert_eq!(request.headers()[X_MBX_APIKEY], "key");
+    }
+
+    fn level(price: &str, quantity: &str) -> (d128, d128) {
+        (price.parse().unwrap(), quantity.parse().unwrap())
+    }
+
+    #[test]
+    fn orderbook_levels_ignore_trailing_elements() {
+        let json = r#"{
+            "lastUpdateId": 1027024,
+            "bids": [["4.00000000", "431.00000000", []]],
+            "asks": [["4.00000200", "12.00000000", [], "ignored"], ["4.00000300", "1.5"]]
+        }"#;
+        let orderbook: Orderbook = serde_json::from_str(json).unwrap();
+
+        assert_eq!(orderbook.last_update_id, 1027024);
+        assert_eq!(orderbook.bids, vec![level("4", "431")]);
+        assert_eq!(orderbook.asks, vec![level("4.000002", "12"), level("4.000003", "1.5")]);
+    }
+
+    #[test]
+    fn depth_update_levels_deserialize() {
+        let json = r#"{
+            "e": "depthUpdate",
+            "E": 123456789,
+            "s": "BNBBTC",
+            "U": 157,
+            "u": 160,
+            "b": [["0.0024", "10"]],
+            "a": [["0.0026", "100", []]]
+        }"#;
+        let update: DepthUpdate = serde_json::from_str(json).unwrap();
+
+        assert_eq!(update.bids, vec![level("0.0024", "10")]);
+        assert_eq!(update.asks, vec![level("0.0026", "100")]);
+    }
+
+    #[test]
+    fn depth_levels_need_a_price_and_quantity() {
+        let json = r#"{"lastUpdateId": 1, "bids": [["4.00000000"]], "asks": []}"#;
+        assert!(serde_json::from_str::<Orderbook>(json).is_err());
+    }
+
+    #[test]
+    fn currencies_round_trip() {
+        util::assert_round_trips::<Currency>(&[("btc", "BTC"), ("BTC", "BTC"), ("Usdt", "USDT")]);
+    }
+
+    #[test]
+    fn currency_pairs_round_trip() {
+        util::assert_round_trips::<CurrencyPair>(&[
+            ("ethbtc", "ETHBTC"),
+            ("BTCUSDT", "BTCUSDT"),
+            ("bnbBusd", "BNBBUSD"),
+            ("XRPBNB", "XRPBNB"),
+        ]);
+        let pair: CurrencyPair = "BTCUSDT".parse().unwrap();
+        assert_eq!(pair.base().to_string(), "BTC");
+        assert_eq!(pair.quote().to_string(), "USDT");
+    }
+
+    #[test]
+    fn uncommon_quote_currency_error_names_the_symbol() {
+        let error = "ETHDAI".parse::<CurrencyPair>().unwrap_err();
+        assert!(error.to_string().contains("\"ETHDAI\""));
+    }
+
+    #[test]
+    fn exchange_info_splits_any_listed_symbol() {
+        let product = ProductInfo {
+            symbol: "ETHDAI".to_owned(),
+            status: SymbolStatus::Trading,
+            base: "ETH".parse().unwrap(),
+            base_precision: 8,
+            quote: "DAI".parse().unwrap(),
+            quote_precision: 8,
+            order_types: vec![OrderInstruction::Limit],
+            iceberg_allowed: false,
+            filters: Vec::new(),
+        };
+        let exchange_info = ExchangeInfo {
+            timezone: "UTC".to_owned(),
+            server_time: 0,
+            rate_limits: Vec::new(),
+            exchange_filters: Vec::new(),
+            products: vec![product],
+        };
+
+        let pair = exchange_info.currency_pair("ethdai").unwrap();
+        assert_eq!(pair.to_string(), "ETHDAI");
+        assert_eq!(pair.quote().to_string(), "DAI");
+        assert_eq!(exchange_info.currency_pair("ETHBTC"), None);
+    }
+
+    const ADDRESS: &str = "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2";
+
+    #[test]
+    fn withdraw_sends_to_whitelisted_address() {
+        let mut client = MockClient::new(r#"{"id":"7213fea8e94b4a5593d507237e5a555b"}"#);
+        let guard = WhitelistGuard::new(vec![ADDRESS]);
+        let btc: Currency = "BTC".parse().unwrap();
+        let amount = "0.5".parse().unwrap();
+        let host = host(Environment::Production);
+
+        let id = withdraw(&mut client, &host, &credential(), &guard, &btc, amount, ADDRESS, None)
+            .unwrap();
+
+        assert_eq!(id, "7213fea8e94b4a5593d507237e5a555b");
+        assert_eq!(client.sent.len(), 1);
+        let uri = client.sent[0].uri().to_string();
+        assert!(uri.contains(&format!("address={}", ADDRESS)));
+    }
+
+    #[test]
+    fn withdraw_refuses_address_not_whitelisted_without_sending() {
+        let mut client = MockClient::new(r#"{"id":"7213fea8e94b4a5593d507237e5a555b"}"#);
+        let guard = WhitelistGuard::new(vec![ADDRESS]);
+        let btc: Currency = "BTC".parse().unwrap();
+        let amount = "0.5".parse().unwrap();
+        let host = host(Environment::Production);
+        let address = "3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy";
+
+        let error = withdraw(&mut client, &host, &credential(), &guard, &btc, amount, address, None)
+            .unwrap_err();
+
+        match error.downcast_ref::<CcexError>() {
+            Some(&CcexError::AddressNotWhitelisted(_)) => (),
+            other => panic!("expected AddressNotWhitelisted, got {:?}", other),
+        }
+        assert!(client.sent.is_empty());
+    }
+
+    /// Assert that `response` fails with a `Venue` error quoting `expected` from its body.
+    fn assert_venue_error_quotes(response: &http::Response<String>, expected: &str) {
+        let error = deserialize_public_response::<ExchangeInfo>(response).unwrap_err();
+        match error.downcast_ref::<CcexError>() {
+            Some(&CcexError::Venue { code: None, ref message }) => {
+                assert!(message.contains(expected), "{:?} doesn't quote the body", message);
+                assert!(!message.contains("expected value"), "{:?} is a serde error", message);
+            }
+            other => panic!("expected Venue, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn maintenance_page_is_reported_with_a_snippet() {
+        let response = http::Response::builder()
+            .status(200)
+            .body(util::MAINTENANCE_PAGE.to_owned())
+            .unwrap();
+        assert_venue_error_quotes(&response, "down for maintenance");
+    }
+
+    #[test]
+    fn cloudflare_error_is_reported_with_its_status_and_a_snippet() {
+        let response = http::Response::builder()
+            .status(502)
+            .body(util::CLOUDFLARE_PAGE.to_owned())
+            .unwrap();
+        assert_venue_error_quotes(&response, "cloudflare");
+        assert_venue_error_quotes(&response, "status 502");
+    }
+
+    /// A product listing as Binance sends it, with the filters this crate understands.
+    fn product_json(symbol: &str, base: &str, quote: &str) -> String {
+        format!(
+            r#"{{"symbol":"{}","status":"TRADING","baseAsset":"{}","baseAssetPrecision":8,
+            "quoteAsset":"{}","quotePrecision":8,"orderTypes":["LIMIT","MARKET"],
+            "icebergAllowed":true,"filters":[
+                {{"filterType":"PRICE_FILTER","minPrice":"0.00000100",
+                "maxPrice":"100000.00000000","tickSize":"0.00000100"}},
+                {{"filterType":"LOT_SIZE","minQty":"0.00100000","maxQty":"100000.00000000",
+                "stepSize":"0.00100000"}},
+                {{"filterType":"MIN_NOTIONAL","minNotional":"0.00100000"}}]}}"#,
+            symbol, base, quote
+        )
+    }
+
+    fn exchange_info_json(products: &[String]) -> String {
+        format!(
+            r#"{{"timezone":"UTC","serverTime":1508631584636,"rateLimits":[
+                {{"rateLimitType":"REQUEST_WEIGHT","interval":"MINUTE","limit":1200}}],
+            "exchangeFilters":[],"symbols":[{}]}}"#,
+            products.join(",")
+        )
+    }
+
+    #[test]
+    fn symbol_info_is_requested_by_symbol() {
+        let products = [product_json("BNBBTC", "BNB", "BTC")];
+        let mut client = MockClient::new(&exchange_info_json(&products));
+        let product: CurrencyPair = "BNBBTC".parse().unwrap();
+
+        let product_info =
+            get_symbol_info(&mut client, &host(Environment::Production), &product).unwrap();
+
+        assert_eq!(product_info.symbol, "BNBBTC");
+        assert_eq!(product_info.currency_pair(), product);
+        assert_eq!(client.sent[0].uri().path(), "/api/v3/exchangeInfo");
+        assert_eq!(client.sent[0].uri().query(), Some("symbol=BNBBTC"));
+    }
+
+    #[test]
+    fn symbol_info_is_the_requested_product_even_if_others_are_listed() {
+        let products = [
+            product_json("ETHBTC", "ETH", "BTC"),
+            product_json("BNBBTC", "BNB", "BTC"),
+        ];
+        let mut client = MockClient::new(&exchange_info_json(&products));
+        let host = host(Environment::Production);
+
+        let bnb_btc = "BNBBTC".parse().unwrap();
+        let product_info = get_symbol_info(&mut client, &host, &bnb_btc).unwrap();
+        assert_eq!(product_info.symbol, "BNBBTC");
+
+        let ltc_btc = "LTCBTC".parse().unwrap();
+        let error = get_symbol_info(&mut client, &host, &ltc_btc).unwrap_err();
+        assert!(error.to_string().contains("isn't listed"));
+    }
+
+    #[test]
+    fn insufficient_balance_is_insufficient_funds() {
+        let body = r#"{"code":-2010,
+            "msg":"Account has insufficient balance for requested action."}"#;
+        let response = http::Response::builder()
+            .status(400)
+            .body(body.to_owned())
+            .unwrap();
+        match classify_error(&response) {
+            CcexError::InsufficientFunds(ref message) => {
+                assert!(message.contains("insufficient balance"))
+            }
+            other => panic!("expected InsufficientFunds, got {:?}", other),
+        }
+    }
+
+    fn assert_rejected_by(result: Result<(), Error>, filter: &str, expected: &str) {
+        match result.unwrap_err().downcast_ref::<CcexError>() {
+            Some(&CcexError::OrderRejected(ref message)) => {
+                assert!(message.contains(filter), "{:?} doesn't name {}", message, filter);
+                assert!(message.contains(expected), "{:?} doesn't say {}", message, expected);
+            }
+            other => panic!("expected OrderRejected, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn price_off_the_tick_size_is_rejected_by_the_price_filter() {
+        let product_info: ProductInfo =
+            serde_json::from_str(&product_json("BNBBTC", "BNB", "BTC")).unwrap();
+        let price = "0.0000015".parse().unwrap();
+        let quantity = "1".parse().unwrap();
+
+        let result = product_info.validate_order(price, quantity);
+        assert_rejected_by(result, "PRICE_FILTER", "isn't a multiple of");
+    }
+
+    #[test]
+    fn notional_below_the_minimum_is_rejected_by_the_min_notional_filter() {
+        let product_info: ProductInfo =
+            serde_json::from_str(&product_json("BNBBTC", "BNB", "BTC")).unwrap();
+        let price = "0.0001".parse().unwrap();
+        let quantity = "1".parse().unwrap();
+
+        let result = product_info.validate_order(price, quantity);
+        assert_rejected_by(result, "MIN_NOTIONAL", "is below the minimum");
+    }
+
+    fn order_json(
+        order_id: u64,
+        client_order_id: &str,
+        price: &str,
+        original_quantity: &str,
+        executed_quantity: &str,
+        status: &str,
+        fills: &str,
+    ) -> String {
+        format!(
+            r#"{{"symbol":"BNBBTC","orderId":{},"clientOrderId":"{}","transactTime":1507725176595,
+            "price":"{}","origQty":"{}","executedQty":"{}","status":"{}","timeInForce":"GTC",
+            "type":"LIMIT","side":"BUY","fills":[{}]}}"#,
+            order_id, client_order_id, price, original_quantity, executed_quantity, status, fills
+        )
+    }
+
+    fn cancel_replace(client: &mut MockClient, mode: CancelReplaceMode) -> CancelReplaceResult {
+        let product: CurrencyPair = "BNBBTC".parse().unwrap();
+        cancel_replace_order(
+            client,
+            &host(Environment::Production),
+            &credential(),
+            &product,
+            27,
+            "0.1".parse().unwrap(),
+            "1".parse().unwrap(),
+            TimeInForce::GoodTillCancelled,
+            Side::Buy,
+            None,
+            mode,
+        ).unwrap()
+    }
+
+    #[test]
+    fn cancel_replace_reports_both_halves_succeeding() {
+        let body = format!(
+            r#"{{"cancelResult":"SUCCESS","newOrderResult":"SUCCESS",
+            "cancelResponse":{{"symbol":"BNBBTC","origClientOrderId":"old","orderId":27,
+                "clientOrderId":"cancel"}},
+            "newOrderResponse":{}}}"#,
+            order_json(28, "new", "0.1", "1", "0", "NEW", "")
+        );
+        let mut client = MockClient::new(&body);
+
+        let result = cancel_replace(&mut client, CancelReplaceMode::StopOnFailure);
+
+        let uri = client.sent[0].uri().to_string();
+        assert!(uri.contains("cancelReplaceMode=STOP_ON_FAILURE"));
+        assert!(uri.contains("cancelOrderId=27"));
+        assert_eq!(result.cancel_result, OperationResult::Success);
+        assert_eq!(result.new_order_result, OperationResult::Success);
+        match result.cancel_response {
+            Outcome::Done(ref cancellation) => assert_eq!(cancellation.order_id, 27),
+            ref other => panic!("expected the cancellation, got {:?}", other),
+        }
+        match result.new_order_response {
+            Some(Outcome::Done(ref order)) => assert_eq!(order.order_id, 28),
+            ref other => panic!("expected the new order, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn cancel_replace_stopped_by_a_failed_cancel_isnt_an_error() {
+        let body = r#"{"code":-2022,"msg":"Order cancel-replace failed.","data":{
+            "cancelResult":"FAILURE","newOrderResult":"NOT_ATTEMPTED",
+            "cancelResponse":{"code":-2011,"msg":"Unknown order sent."},
+            "newOrderResponse":null}}"#;
+        let mut client = MockClient::queued(vec![MockResponse::new(400, body)]);
+
+        let result = cancel_replace(&mut client, CancelReplaceMode::StopOnFailure);
+
+        assert_eq!(result.cancel_result, OperationResult::Failure);
+        assert_eq!(result.new_order_result, OperationResult::NotAttempted);
+        assert_eq!(
+            result.cancel_response,
+            Outcome::Failed { code: -2011, msg: "Unknown order sent.".to_owned() }
+        );
+        assert_eq!(result.new_order_response, None);
+    }
+
+    #[test]
+    fn cancel_replace_allowing_failure_places_the_new_order_after_a_failed_cancel() {
+        let body = format!(
+            r#"{{"code":-2021,"msg":"Order cancel-replace partially failed.","data":{{
+            "cancelResult":"FAILURE","newOrderResult":"SUCCESS",
+            "cancelResponse":{{"code":-2011,"msg":"Unknown order sent."}},
+            "newOrderResponse":{}}}}}"#,
+            order_json(28, "new", "0.1", "1", "0", "NEW", "")
+        );
+        let mut client = MockClient::queued(vec![MockResponse::new(409, &body)]);
+
+        let result = cancel_replace(&mut client, CancelReplaceMode::AllowFailure);
+
+        assert!(client.sent[0].uri().to_string().contains("cancelReplaceMode=ALLOW_FAILURE"));
+        assert_eq!(result.cancel_result, OperationResult::Failure);
+        assert_eq!(result.new_order_result, OperationResult::Success);
+        match result.new_order_response {
+            Some(Outcome::Done(ref order)) => assert_eq!(order.order_id, 28),
+            ref other => panic!("expected the new order, got {:?}", other),
+        }
+    }
+
+    fn test_limit_order(client: &mut MockClient) -> Result<(), Error> {
+        let product: CurrencyPair = "BNBBTC".parse().unwrap();
+        test_order(
+            client,
+            &host(Environment::Production),
+            &credential(),
+            &product,
+            "0.1".parse().unwrap(),
+            "1".parse().unwrap(),
+            TimeInForce::GoodTillCancelled,
+            Side::Buy,
+            None,
+        )
+    }
+
+    #[test]
+    fn test_order_accepted_is_ok() {
+        let mut client = MockClient::new("{}");
+
+        test_limit_order(&mut client).unwrap();
+
+        assert_eq!(client.sent.len(), 1);
+        assert_eq!(client.sent[0].uri().path(), "/api/v3/order/test");
+    }
+
+    #[test]
+    fn test_order_failing_a_filter_is_rejected() {
+        let body = r#"{"code":-1013,"msg":"Filter failure: PRICE_FILTER"}"#;
+        let mut client = MockClient::queued(vec![MockResponse::new(400, body)]);
+
+        let error = test_limit_order(&mut client).unwrap_err();
+
+        match error.downcast_ref::<CcexError>() {
+            Some(&CcexError::OrderRejected(ref message)) => {
+                assert!(message.contains("PRICE_FILTER"))
+            }
+            other => panic!("expected OrderRejected, got {:?}", other),
+        }
+    }
+
+    fn fill_json(trade_id: u64, price: &str, quantity: &str, commission: &str) -> String {
+        format!(
+            r#"{{"tradeId":{},"price":"{}","qty":"{}","commission":"{}","commissionAsset":"BNB"}}"#,
+            trade_id, price, quantity, commission
+        )
+    }
+
+    fn place_limit_buy(client: &mut MockClient, client_order_id: Option<&str>) -> Order {
+        let product: CurrencyPair = "BNBBTC".parse().unwrap();
+        place_limit_order(
+            client,
+            &host(Environment::Production),
+            &credential(),
+            &product,
+            "0.0013".parse().unwrap(),
+            "6".parse().unwrap(),
+            TimeInForce::GoodTillCancelled,
+            Side::Buy,
+            client_order_id,
+        ).unwrap()
+    }
+
+    #[test]
+    fn full_order_response_has_every_fill() {
+        let fills = [
+            fill_json(1, "0.00110000", "1.00000000", "0.00100000"),
+            fill_json(2, "0.00120000", "2.00000000", "0.00200000"),
+            fill_json(3, "0.00130000", "3.00000000", "0.00300000"),
+        ];
+        let body = order_json(28, "new", "0.0013", "6", "6.00000000", "FILLED", &fills.join(","));
+        let mut client = MockClient::new(&body);
+
+        let order = place_limit_buy(&mut client, None);
+
+        assert!(client.sent[0].uri().to_string().contains("newOrderRespType=FULL"));
+        let fills: Vec<(d128, d128, d128)> = order
+            .fills
+            .iter()
+            .map(|fill| (fill.price, fill.quantity, fill.commission))
+            .collect();
+        assert_eq!(
+            fills,
+            vec![
+                ("0.0011".parse().unwrap(), "1".parse().unwrap(), "0.001".parse().unwrap()),
+                ("0.0012".parse().unwrap(), "2".parse().unwrap(), "0.002".parse().unwrap()),
+                ("0.0013".parse().unwrap(), "3".parse().unwrap(), "0.003".parse().unwrap()),
+            ]
+        );
+        let filled = order.fills.iter().fold(d128::zero(), |sum, fill| sum + fill.quantity);
+        assert_eq!(order.executed_quantity, filled);
+        assert!(order.remaining_quantity().is_zero());
+    }
+
+    #[test]
+    fn open_oco_orders_deserialize() {
+        // From Binance's REST API documentation, "Query Open OCO (USER_DATA)".
+        let body = r#"[{"orderListId":31,"contingencyType":"OCO","listStatusType":"EXEC_STARTED",
+            "listOrderStatus":"EXECUTING","listClientOrderId":"wuB13fmulKj3YjdqWEcsnp",
+            "transactionTime":1565246080644,"symbol":"LTCBTC","orders":[
+                {"symbol":"LTCBTC","orderId":4,"clientOrderId":"r3EH2N76dHfLoSZWIUw1bT"},
+                {"symbol":"LTCBTC","orderId":5,"clientOrderId":"Cv1SnyPD3qhqpbjpYEHbd2"}]}]"#;
+        let mut client = MockClient::new(body);
+
+        let oco_orders =
+            get_open_oco_orders(&mut client, &host(Environment::Production), &credential())
+                .unwrap();
+
+        assert_eq!(client.sent[0].uri().path(), "/api/v3/openOrderList");
+        assert_eq!(oco_orders.len(), 1);
+        let oco_order = &oco_orders[0];
+        assert_eq!(oco_order.order_list_id, 31);
+        assert_eq!(oco_order.list_status_type, ListStatusType::ExecStarted);
+        assert_eq!(oco_order.list_order_status, ListOrderStatus::Executing);
+        let order_ids: Vec<u64> = oco_order.orders.iter().map(|order| order.order_id).collect();
+        assert_eq!(order_ids, vec![4, 5]);
+    }
+
+    #[test]
+    fn cancel_oco_order_deletes_the_order_list() {
+        // From Binance's REST API documentation, "Cancel OCO (TRADE)", with the order reports
+        // cut down; they aren't kept.
+        let body = r#"{"orderListId":0,"contingencyType":"OCO","listStatusType":"ALL_DONE",
+            "listOrderStatus":"ALL_DONE","listClientOrderId":"C3wyj4WVEktd7u9aVBRXcN",
+            "transactionTime":1574040868128,"symbol":"LTCBTC","orders":[
+                {"symbol":"LTCBTC","orderId":2,"clientOrderId":"pO9ufTiFGg3nw2fOdgeOXa"},
+                {"symbol":"LTCBTC","orderId":3,"clientOrderId":"TXOvglzXuaubXAaENpaRCB"}],
+            "orderReports":[
+                {"symbol":"LTCBTC","orderId":2,"status":"CANCELED"},
+                {"symbol":"LTCBTC","orderId":3,"status":"CANCELED"}]}"#;
+        let mut client = MockClient::new(body);
+        let product: CurrencyPair = "LTCBTC".parse().unwrap();
+
+        let oco_order = cancel_oco_order(
+            &mut client,
+            &host(Environment::Production),
+            &credential(),
+            0,
+            &product,
+        ).unwrap();
+
+        let request = &client.sent[0];
+        assert_eq!(request.method(), http::Method::DELETE);
+        assert_eq!(request.uri().path(), "/api/v3/orderList");
+        let query = request.uri().query().unwrap();
+        assert!(query.split('&').any(|param| param == "orderListId=0"));
+        assert!(query.split('&').any(|param| param == "symbol=LTCBTC"));
+        assert_eq!(oco_order.order_list_id, 0);
+        assert_eq!(oco_order.list_status_type, ListStatusType::AllDone);
+        assert_eq!(oco_order.list_order_status, ListOrderStatus::AllDone);
+    }
+
+    #[test]
+    fn weight_tracking_keeps_the_last_used_weight_reported() {
+        let exchange_info: ExchangeInfo = serde_json::from_str(&exchange_info_json(&[])).unwrap();
+        let client = MockClient::queued(vec![
+            MockResponse::new(200, "{}").with_header("X-MBX-USED-WEIGHT-1M", "100"),
+            MockResponse::new(200, "{}").with_header("X-MBX-USED-WEIGHT-1M", "garbage"),
+            MockResponse::new(200, "{}"),
+        ]);
+        let mut client = WeightTracking::new(client, &exchange_info);
+        let request = http::request::Builder::new()
+            .uri(format!("{}/api/v3/ping", host(Environment::Production)))
+            .body(String::new())
+            .unwrap();
+        assert_eq!(client.limit, 1200);
+
+        // `used` rather than `remaining_weight`, which resets if the minute ticks over mid-test.
+        client.send(&request).unwrap();
+        assert_eq!(client.used, 100);
+        client.send(&request).unwrap();
+        assert_eq!(client.used, 100);
+        client.send(&request).unwrap();
+        assert_eq!(client.used, 100);
+        assert_eq!(client.client.sent.len(), 3);
+    }
+
+    /// The value of `name` in the query string of `request`.
+    fn query_param<'a>(request: &'a http::Request<String>, name: &str) -> Option<&'a str> {
+        request
+            .uri()
+            .query()?
+            .split('&')
+            .filter_map(|param| {
+                let mut parts = param.splitn(2, '=');
+                match (parts.next(), parts.next()) {
+                    (Some(key), Some(value)) if key == name => Some(value),
+                    _ => None,
+                }
+            })
+            .next()
+    }
+
+    #[test]
+    fn open_orders_deserialize() {
+        let body = format!(
+            "[{},{}]",
+            order_json(1, "first", "0.00100000", "2.00000000", "0.00000000", "NEW", ""),
+            order_json(2, "second", "0.002", "3", "1", "PARTIALLY_FILLED", "")
+        );
+        let mut client = MockClient::new(&body);
+        let product: CurrencyPair = "BNBBTC".parse().unwrap();
+
+        let orders = get_open_orders(
+            &mut client,
+            &host(Environment::Production),
+            &credential(),
+            Some(product),
+        ).unwrap();
+
+        assert_eq!(client.sent[0].uri().path(), "/api/v3/openOrders");
+        assert_eq!(query_param(&client.sent[0], "symbol"), Some("BNBBTC"));
+        let orders: Vec<(&str, d128, d128, OrderStatus)> = orders
+            .iter()
+            .map(|order| {
+                let client_order_id = order.client_order_id.as_str();
+                (client_order_id, order.price, order.original_quantity, order.status)
+            })
+            .collect();
+        assert_eq!(
+            orders,
+            vec![
+                ("first", "0.001".parse().unwrap(), "2".parse().unwrap(), OrderStatus::New),
+                (
+                    "second",
+                    "0.002".parse().unwrap(),
+                    "3".parse().unwrap(),
+                    OrderStatus::PartiallyFilled,
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn historical_trades_send_the_key_without_signing() {
+        // From Binance's REST API documentation, "Old trade lookup (MARKET_DATA)".
+        let body = r#"[{"id":28457,"price":"4.00000100","qty":"12.00000000",
+            "quoteQty":"48.000012","time":1499865549590,"isBuyerMaker":true,
+            "isBestMatch":true}]"#;
+        let mut client = MockClient::new(body);
+        let product: CurrencyPair = "BNBBTC".parse().unwrap();
+
+        let trades = get_historical_trades(
+            &mut client,
+            &host(Environment::Production),
+            "key",
+            &product,
+            Some(28457),
+            Some(1),
+        ).unwrap();
+
+        let request = &client.sent[0];
+        assert_eq!(request.uri().path(), "/api/v1/historicalTrades");
+        assert_eq!(request.headers()[X_MBX_APIKEY], "key");
+        assert_eq!(query_param(request, "fromId"), Some("28457"));
+        assert_eq!(query_param(request, "signature"), None);
+        assert_eq!(query_param(request, "timestamp"), None);
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].id, 28457);
+    }
+
+    #[test]
+    fn limit_order_is_placed_with_its_client_order_id() {
+        let body = order_json(28, "my-order-1", "0.0013", "6", "0", "NEW", "");
+        let mut client = MockClient::new(&body);
+
+        let order = place_limit_buy(&mut client, Some("my-order-1"));
+
+        assert_eq!(query_param(&client.sent[0], "newClientOrderId"), Some("my-order-1"));
+        assert_eq!(order.client_order_id, "my-order-1");
+    }
+
+    #[test]
+    fn limit_order_without_a_client_order_id_leaves_it_to_binance() {
+        let body = order_json(28, "6gCrw2kRUAF9CvJDGP16IP", "0.0013", "6", "0", "NEW", "");
+        let mut client = MockClient::new(&body);
+
+        place_limit_buy(&mut client, None);
+
+        assert_eq!(query_param(&client.sent[0], "newClientOrderId"), None);
+    }
+
+    #[test]
+    fn order_is_looked_up_by_its_client_order_id() {
+        let body = order_json(28, "my-order-1", "0.0013", "6", "0", "NEW", "");
+        let mut client = MockClient::new(&body);
+        let product: CurrencyPair = "BNBBTC".parse().unwrap();
+
+        let order = get_order_by_client_order_id(
+            &mut client,
+            &host(Environment::Production),
+            &credential(),
+            "my-order-1",
+            &product,
+        ).unwrap();
+
+        let request = &client.sent[0];
+        assert_eq!(request.uri().path(), "/api/v3/order");
+        assert_eq!(query_param(request, "origClientOrderId"), Some("my-order-1"));
+        assert_eq!(query_param(request, "orderId"), None);
+        assert_eq!(order.order_id, 28);
+    }
+
+    #[test]
+    fn sandbox_is_the_spot_testnet() {
+        assert_eq!(host(Environment::Production).as_str(), API_HOST);
+        assert_eq!(host(Environment::Sandbox).as_str(), TESTNET_API_HOST);
+
+        let products = [product_json("BNBBTC", "BNB", "BTC")];
+        let mut client = MockClient::new(&exchange_info_json(&products));
+        let product: CurrencyPair = "BNBBTC".parse().unwrap();
+        get_symbol_info(&mut client, &host(Environment::Sandbox), &product).unwrap();
+
+        let uri = client.sent[0].uri();
+        assert_eq!(uri.host(), Some("testnet.binance.vision"));
+        assert_eq!(uri.path(), "/api/v3/exchangeInfo");
+    }
+
+    /// An account whose API key can only read.
+    const READ_ONLY_ACCOUNT: &str = r#"{"makerCommission":15,"takerCommission":15,
+        "buyerCommission":0,"sellerCommission":0,"canTrade":false,"canWithdraw":false,
+        "canDeposit":true,"updateTime":123456789,"balances":[]}"#;
+
+    fn assert_auth_error(result: Result<(), Error>) {
+        match result.unwrap_err().downcast_ref::<CcexError>() {
+            Some(&CcexError::Auth(_)) => (),
+            other => panic!("expected Auth, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn read_only_key_places_no_limit_order() {
+        // Only the account is queued; an order request would fail with "no response queued".
+        let mut client = MockClient::queued(vec![MockResponse::new(200, READ_ONLY_ACCOUNT)]);
+        let host = host(Environment::Production);
+        let product: CurrencyPair = "BNBBTC".parse().unwrap();
+
+        let account = get_account_info(&mut client, &host, &credential()).unwrap();
+        let result = account.permissions().require_trade().and_then(|()| {
+            let price = "0.1".parse().unwrap();
+            let quantity = "1".parse().unwrap();
+            let time_in_force = TimeInForce::GoodTillCancelled;
+            place_limit_order(
+                &mut client,
+                &host,
+                &credential(),
+                &product,
+                price,
+                quantity,
+                time_in_force,
+                Side::Buy,
+                None,
+            ).map(|_| ())
+        });
+
+        assert_auth_error(result);
+        assert_eq!(client.sent.len(), 1);
+        assert_eq!(client.sent[0].uri().path(), "/api/v3/account");
+    }
+
+    #[test]
+    fn read_only_key_withdraws_nothing() {
+        let mut client = MockClient::queued(vec![MockResponse::new(200, READ_ONLY_ACCOUNT)]);
+        let host = host(Environment::Production);
+        let guard = WhitelistGuard::new(vec![ADDRESS]);
+        let btc: Currency = "BTC".parse().unwrap();
+        let amount = "0.5".parse().unwrap();
+
+        let account = get_account_info(&mut client, &host, &credential()).unwrap();
+        let result = account.permissions().require_withdraw().and_then(|()| {
+            withdraw(&mut client, &host, &credential(), &guard, &btc, amount, ADDRESS, None)
+                .map(|_| ())
+        });
+
+        assert_auth_error(result);
+        assert_eq!(client.sent.len(), 1);
+    }
+
+    #[cfg(feature = "debug")]
+    #[test]
+    fn debug_signature_matches_binance_example() {
+        use signer::tests::{BINANCE_QUERY, BINANCE_SECRET, BINANCE_SIGNATURE};
+        let credential = Credential {
+            key: "key".to_owned(),
+            secret: BINANCE_SECRET.to_owned(),
+        };
+
+        let debug = debug_signature(&credential, BINANCE_QUERY).unwrap();
+
+        assert_eq!(debug.signed, BINANCE_QUERY);
+        assert_eq!(debug.signature, BINANCE_SIGNATURE);
+        let query = format!("{}&signature={}", BINANCE_QUERY, BINANCE_SIGNATURE);
+        assert_eq!(debug.query, Some(query));
+    }
+
+    #[test]
+    fn dust_transfer_lists_the_converted_assets() {
+        // From Binance's REST API documentation, "Dust Transfer (USER_DATA)", less its LTC and
+        // USDT entries. XRP is requested but not converted, so it's missing from the result.
+        let body = r#"{"totalServiceCharge":"0.00500000","totalTransfered":"0.25000000",
+            "transferResult":[{"amount":"0.03000000","fromAsset":"ETH",
+                "operateTime":1563368549307,"serviceChargeAmount":"0.00500000",
+                "tranId":2970932918,"transferedAmount":"0.25000000"}]}"#;
+        let mut client = MockClient::new(body);
+        let assets: Vec<Currency> = vec!["ETH".parse().unwrap(), "XRP".parse().unwrap()];
+
+        let result =
+            dust_transfer(&mut client, &host(Environment::Production), &credential(), &assets)
+                .unwrap();
+
+        let request = &client.sent[0];
+        assert_eq!(request.method(), http::Method::POST);
+        assert_eq!(request.uri().path(), "/sapi/v1/asset/dust");
+        let query = request.uri().query().unwrap();
+        let sent: Vec<&str> =
+            query.split('&').filter(|param| param.starts_with("asset=")).collect();
+        assert_eq!(sent, vec!["asset=ETH", "asset=XRP"]);
+        assert_eq!(result.total_transfered, "0.25".parse().unwrap());
+        assert_eq!(result.transfer_result.len(), 1);
+        let transfer = &result.transfer_result[0];
+        assert_eq!(transfer.from_asset, assets[0]);
+        assert_eq!(transfer.amount, "0.03".parse().unwrap());
+        assert_eq!(transfer.service_charge_amount, "0.005".parse().unwrap());
+    }
+
+    #[test]
+    fn dust_transfer_needs_an_asset() {
+        let mut client = MockClient::queued(vec![]);
+
+        let result = dust_transfer(&mut client, &host(Environment::Production), &credential(), &[]);
+
+        assert!(result.is_err());
+        assert!(client.sent.is_empty());
+    }
+
+    #[test]
+    fn order_is_looked_up_by_its_order_id() {
+        let body = order_json(28, "my-order-1", "0.0013", "6", "2", "PARTIALLY_FILLED", "");
+        let mut client = MockClient::new(&body);
+        let product: CurrencyPair = "BNBBTC".parse().unwrap();
+
+        let order =
+            get_order(&mut client, &host(Environment::Production), &credential(), 28, &product)
+                .unwrap();
+
+        let request = &client.sent[0];
+        assert_eq!(request.method(), http::Method::GET);
+        assert_eq!(request.uri().path(), "/api/v3/order");
+        assert_eq!(query_param(request, "symbol"), Some("BNBBTC"));
+        assert_eq!(query_param(request, "orderId"), Some("28"));
+        assert_eq!(query_param(request, "origClientOrderId"), None);
+        assert_eq!(order.order_id, 28);
+        assert_eq!(order.status, OrderStatus::PartiallyFilled);
+        assert_eq!(order.remaining_quantity(), "4".parse().unwrap());
+    }
+
+    #[test]
+    fn unknown_order_is_a_venue_error() {
+        let body = r#"{"code":-2013,"msg":"Order does not exist."}"#;
+        let mut client = MockClient::queued(vec![MockResponse::new(400, body)]);
+        let product: CurrencyPair = "BNBBTC".parse().unwrap();
+
+        let error =
+            get_order(&mut client, &host(Environment::Production), &credential(), 28, &product)
+                .unwrap_err();
+
+        match error.downcast_ref::<CcexError>() {
+            Some(&CcexError::Venue { code: Some(-2013), ref message }) => {
+                assert_eq!(message, "Order does not exist.")
+            }
+            other => panic!("expected Venue with code -2013, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn validated_limit_order_is_rounded_to_the_product_precision() {
+        let mut product_info: ProductInfo =
+            serde_json::from_str(&product_json("BNBBTC", "BNB", "BTC")).unwrap();
+        product_info.quote_precision = 6;
+        product_info.base_precision = 3;
+        let body = order_json(28, "new", "0.001234", "1.234", "0", "NEW", "");
+        let mut client = MockClient::new(&body);
+        let product: CurrencyPair = "BNBBTC".parse().unwrap();
+
+        place_validated_limit_order(
+            &mut client,
+            &host(Environment::Production),
+            &credential(),
+            &product_info,
+            &product,
+            "0.0012345".parse().unwrap(),
+            "1.23456".parse().unwrap(),
+            TimeInForce::GoodTillCancelled,
+            Side::Buy,
+            None,
+        ).unwrap();
+
+        // A buy price is rounded down, as is any quantity.
+        assert_eq!(query_param(&client.sent[0], "price"), Some("0.001234"));
+        assert_eq!(query_param(&client.sent[0], "quantity"), Some("1.234"));
+    }
+}
@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+/// An error that callers may want to react to, regardless of which exchange it came from.
+///
+/// Requests return `failure::Error`; downcast it to `CcexError` to find out what went wrong.
+///
+/// ```rust,ignore
+/// match binance::get_account_info(&mut client, &host, &credential) {
+///     Err(ref e) => match e.downcast_ref::<CcexError>() {
+///         Some(&CcexError::RateLimited { retry_after }) => back_off(retry_after),
+///         Some(&CcexError::Auth(_)) => refresh_credential(),
+///         _ => (),
+///     },
+///     Ok(account) => (),
+/// }
+/// ```
+#[derive(Debug, Fail)]
+pub enum CcexError {
+    /// The request couldn't be sent or the response couldn't be read.
+    #[fail(display = "transport error: {}", _0)]
+    Transport(String),
+
+    /// The response didn't have the expected shape.
+    #[fail(display = "failed to deserialize: {}", _0)]
+    Deserialize(String),
+
+    /// The credential was rejected: a bad key, a bad signature, or missing permissions.
+    #[fail(display = "authentication failed: {}", _0)]
+    Auth(String),
+
+    /// The request's nonce was reused or too low. `expected` is the nonce the exchange asked for,
+    /// if it said.
+    #[fail(display = "invalid nonce: {}", message)]
+    InvalidNonce {
+        expected: Option<u64>,
+        message: String,
+    },
+
+    /// Too many requests. `retry_after` is how long the exchange asked to wait, if it said.
+    #[fail(display = "rate limited")]
+    RateLimited { retry_after: Option<Duration> },
+
+    /// There weren't enough funds to place the order.
+    #[fail(display = "insufficient funds: {}", _0)]
+    InsufficientFunds(String),
+
+    /// The order was refused, e.g. for a bad price or quantity.
+    #[fail(display = "order rejected: {}", _0)]
+    OrderRejected(String),
+
+    /// A withdrawal was refused by a [`WhitelistGuard`](struct.WhitelistGuard.html) before
+    /// anything was sent, because the address isn't on its allowlist.
+    #[fail(display = "address isn't whitelisted: {}", _0)]
+    AddressNotWhitelisted(String),
+
+    /// Any other error reported by the exchange.
+    #[fail(display = "({:?}) {}", code, message)]
+    Venue {
+        code: Option<i64>,
+        message: String,
+    },
+}
+
+/// Phrases exchanges use when there isn't enough balance for a request, in lowercase.
+const INSUFFICIENT_FUNDS_PHRASES: &[&str] = &[
+    // Binance: "Account has insufficient balance for requested action."
+    "insufficient balance",
+    // Exmo: "Error 50052: Insufficient funds"
+    "insufficient funds",
+    // Liqui: "It is not enough BTC in the account for sale."
+    "it is not enough",
+];
+
+/// Whether an exchange's error `message` means there weren't enough funds.
+pub(crate) fn is_insufficient_funds(message: &str) -> bool {
+    let message = message.to_lowercase();
+    INSUFFICIENT_FUNDS_PHRASES
+        .iter()
+        .any(|phrase| message.contains(phrase))
+}
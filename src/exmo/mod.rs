@@ -1,21 +1,31 @@
-use failure::{Error, ResultExt};
-use hex;
-use hmac::{Hmac, Mac};
+use chrono::{self, DateTime, NaiveDateTime, Utc};
+use failure::Error;
 use http;
 use rust_decimal::Decimal as d128;
 use serde::de::DeserializeOwned;
 use serde::de::{Deserialize, Deserializer, Visitor};
 use serde;
 use serde_json;
-use sha2::Sha512;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::{self, Display, Formatter};
 use std::str::FromStr;
-use {HttpClient, Query};
+use error::is_insufficient_funds;
+use signer::{HmacSha512Hex, Signer};
+use util::{self, format_amount, format_currency, Case};
+#[cfg(feature = "debug")]
+use SignatureDebug;
+use {CcexError, Environment, Host, HttpClient, Query, RoundingMode};
 
-/// Use this as the `host` for REST requests.
+/// Base URL for REST requests. Pass [`host`] to requests rather than this.
 pub const API_HOST: &str = "https://api.exmo.com";
 
+/// The REST host for `environment`. Exmo doesn't have a sandbox, so this is always [`API_HOST`].
+pub fn host(environment: Environment) -> Host {
+    match environment {
+        Environment::Production | Environment::Sandbox => Host(API_HOST.to_owned()),
+    }
+}
+
 /// Credential needed for private API requests.
 #[derive(Debug, Hash, PartialEq, PartialOrd, Eq, Ord, Clone, Deserialize, Serialize)]
 pub struct Credential {
@@ -33,17 +43,20 @@ pub struct Currency(String);
 impl FromStr for Currency {
     type Err = Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Currency(s.to_uppercase()))
+        Ok(Currency(format_currency(s, CURRENCY_CASE)))
     }
 }
 
 impl Display for Currency {
     fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
         let &Currency(ref currency) = self;
-        f.write_str(currency)
+        f.write_str(&format_currency(currency, CURRENCY_CASE))
     }
 }
 
+/// Exmo only accepts currencies like `BTC`.
+const CURRENCY_CASE: Case = Case::Upper;
+
 /// Two currencies; `ETH_BTC`, `BTC_USDT`, etc. Usually represents a product.
 #[derive(Debug, Hash, PartialEq, PartialOrd, Eq, Ord, Clone, Serialize)]
 pub struct CurrencyPair(pub Currency, pub Currency);
@@ -70,6 +83,21 @@ impl Display for CurrencyPair {
     }
 }
 
+impl FromStr for CurrencyPair {
+    type Err = Error;
+
+    /// Parse two currencies separated by an underscore, like `eth_btc`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let currencies: Vec<&str> = s.split('_').collect();
+        match currencies.as_slice() {
+            &[base, quote] if !base.is_empty() && !quote.is_empty() => {
+                Ok(CurrencyPair(base.parse()?, quote.parse()?))
+            }
+            _ => Err(format_err!("{:?} isn't two currencies separated by an underscore", s)),
+        }
+    }
+}
+
 impl<'de> Deserialize<'de> for CurrencyPair {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where D: Deserializer<'de> {
@@ -83,13 +111,8 @@ impl<'de> Deserialize<'de> for CurrencyPair {
 
             fn visit_str<E>(self, pair: &str) -> Result<Self::Value, E>
             where E: serde::de::Error {
-                let currencies: Vec<&str> = pair.split('_').collect();
-                if currencies.len() < 2 {
-                    return Err(E::invalid_value(serde::de::Unexpected::Str(pair), &self));
-                }
-                let base = Currency::from_str(currencies[0]).map_err(serde::de::Error::custom)?;
-                let quote = Currency::from_str(currencies[1]).map_err(serde::de::Error::custom)?;
-                Ok(CurrencyPair(base, quote))
+                pair.parse()
+                    .map_err(|_| E::invalid_value(serde::de::Unexpected::Str(pair), &self))
             }
         }
         deserializer.deserialize_str(CurrencyPairVisitor)
@@ -127,6 +150,16 @@ impl Display for OrderInstruction {
     }
 }
 
+/// How much to buy or sell with a market order.
+#[derive(Debug, Hash, PartialEq, PartialOrd, Eq, Ord, Clone, Copy, Deserialize, Serialize)]
+pub enum MarketOrderSize {
+    /// Quantity of the base currency, e.g. "buy 1 BTC".
+    Quantity(d128),
+
+    /// Amount of the quote currency to spend or receive, e.g. "buy 100 USD of BTC".
+    Total(d128),
+}
+
 /// Market depth.
 #[derive(Debug, Hash, PartialEq, PartialOrd, Eq, Ord, Clone, Deserialize, Serialize)]
 pub struct Orderbook {
@@ -140,6 +173,60 @@ pub struct Orderbook {
     pub bid: Vec<(d128, d128, d128)>,
 }
 
+/// A trade that happened on a product.
+#[derive(Debug, Hash, PartialEq, PartialOrd, Eq, Ord, Clone, Deserialize, Serialize)]
+pub struct PublicTrade {
+    pub trade_id: u64,
+    #[serde(rename = "type")]
+    pub side: Side,
+    pub price: d128,
+    pub quantity: d128,
+
+    /// `price * quantity`, in the quote currency.
+    pub amount: d128,
+
+    /// When the trade happened, in seconds.
+    pub date: u64,
+}
+
+/// Trading limits for a product.
+#[derive(Debug, Hash, PartialEq, PartialOrd, Eq, Ord, Clone, Deserialize, Serialize)]
+pub struct PairSettings {
+    pub min_quantity: d128,
+    pub max_quantity: d128,
+    pub min_price: d128,
+    pub max_price: d128,
+
+    /// Minimum `price * quantity`, in the quote currency.
+    pub min_amount: d128,
+
+    /// Maximum `price * quantity`, in the quote currency.
+    pub max_amount: d128,
+}
+
+impl PairSettings {
+    /// Check a market order's size against these limits. A `Quantity` is checked against the
+    /// min/max quantity and a `Total` against the min/max amount, which Exmo sets separately.
+    ///
+    /// Fails with `CcexError::OrderRejected` saying which limit was broken.
+    pub fn validate_market_order(&self, size: MarketOrderSize) -> Result<(), Error> {
+        let (name, value, min, max) = match size {
+            MarketOrderSize::Quantity(quantity) => {
+                ("quantity", quantity, self.min_quantity, self.max_quantity)
+            }
+            MarketOrderSize::Total(total) => ("total", total, self.min_amount, self.max_amount),
+        };
+        let rejection = if value < min {
+            format!("{} {} is below the minimum of {}", name, value, min)
+        } else if value > max {
+            format!("{} {} is above the maximum of {}", name, value, max)
+        } else {
+            return Ok(());
+        };
+        Err(CcexError::OrderRejected(rejection).into())
+    }
+}
+
 /// Private user info (balances, reserved funds, etc.)
 #[derive(Debug, PartialEq, Eq, Clone, Deserialize, Serialize)]
 pub struct UserInfo {
@@ -154,10 +241,63 @@ pub struct Order {
     pub order_id: i64,
 }
 
+/// An order's fills and what it cost, as returned by `get_order_trades`.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Deserialize, Serialize)]
+pub struct OrderTrades {
+    #[serde(rename = "type")]
+    pub side: Side,
+    pub in_currency: Currency,
+    pub in_amount: d128,
+    pub out_currency: Currency,
+    pub out_amount: d128,
+    pub trades: Vec<OrderTrade>,
+}
+
+/// A single fill of an order.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Deserialize, Serialize)]
+pub struct OrderTrade {
+    pub trade_id: u64,
+    pub order_id: i64,
+    pub pair: CurrencyPair,
+    #[serde(rename = "type")]
+    pub side: Side,
+    pub price: d128,
+    pub quantity: d128,
+
+    /// `price * quantity`, in the quote currency.
+    pub amount: d128,
+    pub commission_amount: d128,
+    pub commission_currency: Currency,
+
+    /// When the fill happened, in seconds.
+    pub date: u64,
+}
+
+/// An ex-code created with `create_excode`. Anyone holding `code` can load its funds into their
+/// account.
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize, Serialize)]
+pub struct ExcodeCreated {
+    pub task_id: String,
+    pub code: String,
+    pub amount: d128,
+    pub currency: Currency,
+
+    /// Balances after the funds were taken out for the ex-code.
+    pub balances: HashMap<Currency, d128>,
+}
+
+/// Funds added to the account by `load_excode`.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Deserialize, Serialize)]
+pub struct ExcodeLoaded {
+    pub task_id: String,
+    pub amount: d128,
+    pub currency: Currency,
+}
+
 /// **Private**. Get account info (account balances, etc.)
 pub fn get_user_info<Client>(
     client: &mut Client,
-    host: &str,
+    host: &Host,
     credential: &Credential,
 ) -> Result<UserInfo, Error>
 where
@@ -166,23 +306,52 @@ where
     let query = {
         let mut query = Query::with_capacity(2);
         query.append_param("nonce", credential.nonce.to_string());
-        query.to_string()
+        query
     };
-    let mut http_request = http::request::Builder::new()
-        .method(http::Method::POST)
-        .uri(format!("{}/v1/user_info?{}", host, query))
-        .body(query)?;
-    sign_private_request(&mut http_request, credential)?;
+    let http_request = util::signed_post(host, "/v1/user_info", query, |request| {
+        sign_private_request(request, credential)
+    })?;
 
     let http_response = client.send(&http_request)?;
 
     deserialize_private_response(&http_response)
 }
 
+/// **Private**. Exmo's current time, to the second. Exmo only reports it as `server_date` in
+/// [`get_user_info`], so this needs a credential and uses a nonce.
+pub fn get_server_time<Client>(
+    client: &mut Client,
+    host: &Host,
+    credential: &Credential,
+) -> Result<DateTime<Utc>, Error>
+where
+    Client: HttpClient,
+{
+    let server_date = get_user_info(client, host, credential)?.server_date;
+    Ok(DateTime::from_utc(NaiveDateTime::from_timestamp(server_date as i64, 0), Utc))
+}
+
+/// **Private**. How far Exmo's clock is ahead of the local clock; negative if it's behind. Only
+/// accurate to about a second, since that's all Exmo reports.
+pub fn clock_skew<Client>(
+    client: &mut Client,
+    host: &Host,
+    credential: &Credential,
+) -> Result<chrono::Duration, Error>
+where
+    Client: HttpClient,
+{
+    util::clock_skew(|| get_server_time(client, host, credential))
+}
+
 /// **Private**. Place a limit order.
+///
+/// Exmo doesn't support client-specified order ids, so there's no way to tell whether a placement
+/// that timed out actually went through. Check your open orders before trying again, or risk
+/// placing the order twice.
 pub fn place_limit_order<Client>(
     client: &mut Client,
-    host: &str,
+    host: &Host,
     credential: &Credential,
     product: &CurrencyPair,
     price: d128,
@@ -196,34 +365,181 @@ where
         let mut query = Query::with_capacity(5);
         query.append_param("nonce", credential.nonce.to_string());
         query.append_param("pair", product.to_string());
-        query.append_param("quantity", quantity.to_string());
-        query.append_param("price", price.to_string());
+        let price_rounding = RoundingMode::for_price(side == Side::Buy);
+        query.append_param("quantity", format_amount(quantity, PRECISION, RoundingMode::Down));
+        query.append_param("price", format_amount(price, PRECISION, price_rounding));
         match side {
             Side::Buy => query.append_param("type", "buy"),
             Side::Sell => query.append_param("type", "sell"),
         }
-        query.to_string()
+        query
     };
 
-    let mut http_request = http::request::Builder::new()
-        .method(http::Method::POST)
-        .uri(format!("{}/v1/order_create?{}", host, query))
-        .body(query)?;
-    sign_private_request(&mut http_request, credential)?;
+    let http_request = util::signed_post(host, "/v1/order_create", query, |request| {
+        sign_private_request(request, credential)
+    })?;
 
-    client.send(&http_request)?;
+    let http_response = client.send(&http_request)?;
 
     // Note: Exmo's `Order` doesn't contain anything useful so we don't need
-    // to use it.
+    // to return it. It's still deserialized so a rejection becomes an error.
+    deserialize_private_response::<Order>(&http_response)?;
+    Ok(())
+}
+
+/// **Private**. Place a market order.
+///
+/// Like limit orders, market orders can't be given a client order id. See [`place_limit_order`].
+pub fn place_market_order<Client>(
+    client: &mut Client,
+    host: &Host,
+    credential: &Credential,
+    product: &CurrencyPair,
+    size: MarketOrderSize,
+    side: Side,
+) -> Result<(), Error>
+where
+    Client: HttpClient,
+{
+    let (instruction, quantity) = match (side, size) {
+        (Side::Buy, MarketOrderSize::Quantity(amount)) => (OrderInstruction::MarketBuy, amount),
+        (Side::Sell, MarketOrderSize::Quantity(amount)) => (OrderInstruction::MarketSell, amount),
+        (Side::Buy, MarketOrderSize::Total(amount)) => (OrderInstruction::MarketBuyTotal, amount),
+        (Side::Sell, MarketOrderSize::Total(amount)) => (OrderInstruction::MarketSellTotal, amount),
+    };
+    let query = {
+        let mut query = Query::with_capacity(5);
+        query.append_param("nonce", credential.nonce.to_string());
+        query.append_param("pair", product.to_string());
+        query.append_param("quantity", format_amount(quantity, PRECISION, RoundingMode::Down));
+        query.append_param("price", "0");
+        query.append_param("type", instruction.to_string());
+        query
+    };
+    let http_request = util::signed_post(host, "/v1/order_create", query, |request| {
+        sign_private_request(request, credential)
+    })?;
+
+    let http_response = client.send(&http_request)?;
+    deserialize_private_response::<Order>(&http_response)?;
     Ok(())
 }
 
+/// **Private**. Place a market order after validating its size against the product's settings.
+///
+/// An order that breaks the limits from [`get_pair_settings`] is never sent. See
+/// [`PairSettings::validate_market_order`].
+pub fn place_validated_market_order<Client>(
+    client: &mut Client,
+    host: &Host,
+    credential: &Credential,
+    pair_settings: &BTreeMap<CurrencyPair, PairSettings>,
+    product: &CurrencyPair,
+    size: MarketOrderSize,
+    side: Side,
+) -> Result<(), Error>
+where
+    Client: HttpClient,
+{
+    pair_settings
+        .get(product)
+        .ok_or_else(|| format_err!("{} isn't listed in the pair settings", product))?
+        .validate_market_order(size)?;
+
+    place_market_order(client, host, credential, product, size, side)
+}
+
+/// **Private**. Get the fills of an order, including the commission paid on each.
+pub fn get_order_trades<Client>(
+    client: &mut Client,
+    host: &Host,
+    credential: &Credential,
+    order_id: i64,
+) -> Result<OrderTrades, Error>
+where
+    Client: HttpClient,
+{
+    let query = {
+        let mut query = Query::with_capacity(2);
+        query.append_param("nonce", credential.nonce.to_string());
+        query.append_param("order_id", order_id.to_string());
+        query
+    };
+    let http_request = util::signed_post(host, "/v1/order_trades", query, |request| {
+        sign_private_request(request, credential)
+    })?;
+
+    let http_response = client.send(&http_request)?;
+
+    deserialize_private_response(&http_response)
+}
+
+/// **Private**. Move `amount` of `currency` out of the account and into a new ex-code.
+///
+/// The code is as good as cash; anyone who has it can load it.
+pub fn create_excode<Client>(
+    client: &mut Client,
+    host: &Host,
+    credential: &Credential,
+    currency: &Currency,
+    amount: d128,
+) -> Result<ExcodeCreated, Error>
+where
+    Client: HttpClient,
+{
+    let query = {
+        let mut query = Query::with_capacity(3);
+        query.append_param("nonce", credential.nonce.to_string());
+        query.append_param("currency", currency.to_string());
+        query.append_param("amount", format_amount(amount, PRECISION, RoundingMode::Down));
+        query
+    };
+    let http_request = util::signed_post(host, "/v1/excode_create", query, |request| {
+        sign_private_request(request, credential)
+    })?;
+
+    let http_response = client.send(&http_request)?;
+
+    deserialize_private_response(&http_response)
+}
+
+/// **Private**. Load the funds held by the ex-code `code` into the account.
+pub fn load_excode<Client>(
+    client: &mut Client,
+    host: &Host,
+    credential: &Credential,
+    code: &str,
+) -> Result<ExcodeLoaded, Error>
+where
+    Client: HttpClient,
+{
+    let query = {
+        let mut query = Query::with_capacity(2);
+        query.append_param("nonce", credential.nonce.to_string());
+        query.append_param("code", code);
+        query
+    };
+    let http_request = util::signed_post(host, "/v1/excode_load", query, |request| {
+        sign_private_request(request, credential)
+    })?;
+
+    let http_response = client.send(&http_request)?;
+
+    deserialize_private_response(&http_response)
+}
+
 /// **Public**. Market depth.
+///
+/// `max_orders` is the number of orders returned on each side of each book. Exmo caps it at
+/// `1000`.
+///
+/// Books are keyed in product order, so iterating over them is the same on every call.
 pub fn get_orderbooks<Client>(
     client: &mut Client,
-    host: &str,
+    host: &Host,
     products: &[&CurrencyPair],
-) -> Result<HashMap<CurrencyPair, Orderbook>, Error>
+    max_orders: u32,
+) -> Result<BTreeMap<CurrencyPair, Orderbook>, Error>
 where
     Client: HttpClient,
 {
@@ -231,7 +547,7 @@ where
     let query = {
         let mut query = Query::with_capacity(2);
         query.append_param("pair", products.as_slice().join(","));
-        query.append_param("limit", "100");
+        query.append_param("limit", max_orders.to_string());
         query.to_string()
     };
     let http_request = http::request::Builder::new()
@@ -244,6 +560,57 @@ where
     deserialize_public_response(&http_response)
 }
 
+/// **Public**. Every product Exmo trades, and its trading limits.
+///
+/// Settings are keyed in product order, so iterating over them is the same on every call.
+pub fn get_pair_settings<Client>(
+    client: &mut Client,
+    host: &Host,
+) -> Result<BTreeMap<CurrencyPair, PairSettings>, Error>
+where
+    Client: HttpClient,
+{
+    let http_request = http::request::Builder::new()
+        .method(http::Method::GET)
+        .uri(format!("{}/v1/pair_settings", host))
+        .body(String::new())?;
+
+    let http_response = client.send(&http_request)?;
+
+    deserialize_public_response(&http_response)
+}
+
+/// **Public**. The most recent trades for each product.
+///
+/// Trades are keyed in product order, so iterating over them is the same on every call.
+pub fn get_trades<Client>(
+    client: &mut Client,
+    host: &Host,
+    products: &[&CurrencyPair],
+) -> Result<BTreeMap<CurrencyPair, Vec<PublicTrade>>, Error>
+where
+    Client: HttpClient,
+{
+    let products: Vec<String> = products.iter().map(ToString::to_string).collect();
+    let query = {
+        let mut query = Query::with_capacity(1);
+        query.append_param("pair", products.as_slice().join(","));
+        query.to_string()
+    };
+    let http_request = http::request::Builder::new()
+        .method(http::Method::GET)
+        .uri(format!("{}/v1/trades?{}", host, query))
+        .body(String::new())?;
+
+    let http_response = client.send(&http_request)?;
+
+    deserialize_public_response(&http_response)
+}
+
+/// Exmo never accepts more than 8 decimal places for a price or quantity. Unlike Binance and
+/// Liqui, its pair settings give no per-product precision, so this is the only rounding applied.
+const PRECISION: u32 = 8;
+
 #[derive(Debug, Hash, PartialEq, PartialOrd, Eq, Ord, Clone, Deserialize, Serialize)]
 struct ErrorResponse {
     pub result: bool,
@@ -255,10 +622,7 @@ fn sign_private_request(
     credential: &Credential,
 ) -> Result<(), Error>
 {
-    let mut mac =
-        Hmac::<Sha512>::new(credential.secret.as_bytes()).map_err(|e| format_err!("{:?}", e))?;
-    mac.input(request.body().as_bytes());
-    let signature = hex::encode(mac.result().code().to_vec());
+    let signature = private_signature(credential, request.body())?;
 
     let headers = request.headers_mut();
     headers.insert("Key", credential.key.clone().parse().unwrap());
@@ -267,11 +631,29 @@ fn sign_private_request(
     Ok(())
 }
 
+fn private_signature(credential: &Credential, body: &str) -> Result<String, Error> {
+    HmacSha512Hex.sign(&credential.secret, body)
+}
+
+/// The body that's signed for a private request and the signature sent in its `Sign` header.
+/// Compare these against the documented example when a request is rejected for its signature.
+#[cfg(feature = "debug")]
+pub fn debug_signature(credential: &Credential, body: &str) -> Result<SignatureDebug, Error> {
+    Ok(SignatureDebug {
+        signed: body.to_owned(),
+        signature: private_signature(credential, body)?,
+        query: None,
+    })
+}
+
 /// Deserialize a response returned from a private HTTP request.
 fn deserialize_private_response<T>(response: &http::Response<String>) -> Result<T, Error>
 where T: DeserializeOwned {
+    util::check_status(response)?;
+    util::require_json(response)?;
     let body = response.body();
-    let response: serde_json::Value = serde_json::from_str(body)?;
+    let response: serde_json::Value = serde_json::from_str(body)
+        .map_err(|e| CcexError::Deserialize(format!("{}: \"{}\"", e, body)))?;
 
     // If the response is an error, it will be a json object containing a
     // `result` equal to `false`.
@@ -287,18 +669,371 @@ where T: DeserializeOwned {
 
     if is_error {
         let error: ErrorResponse = serde_json::from_value(response)
-            .with_context(|_| format!("failed to deserialize: \"{}\"", body))?;
-        Err(format_err!("Server returned: {}", error.error))
+            .map_err(|e| CcexError::Deserialize(format!("{}: \"{}\"", e, body)))?;
+        Err(classify_error(error.error).into())
     } else {
         let response = serde_json::from_value(response)
-            .context(format!("failed to deserialize: \"{}\"", body))?;
+            .map_err(|e| CcexError::Deserialize(format!("{}: \"{}\"", e, body)))?;
         Ok(response)
     }
 }
 
+/// Exmo only reports errors as a message, e.g. `"Error 40017: Wrong api key"`, so sort them by
+/// what the message says.
+fn classify_error(message: String) -> CcexError {
+    let lowercase = message.to_lowercase();
+    if lowercase.contains("nonce") {
+        CcexError::InvalidNonce { expected: None, message }
+    } else if is_insufficient_funds(&message) {
+        CcexError::InsufficientFunds(message)
+    } else if lowercase.contains("authorization error") || lowercase.contains("api key") {
+        CcexError::Auth(message)
+    } else {
+        let code = message
+            .trim_left_matches("Error ")
+            .split(':')
+            .next()
+            .and_then(|code| code.trim().parse().ok());
+        CcexError::Venue { code, message }
+    }
+}
+
 /// Deserialize a response returned from a public HTTP request.
 fn deserialize_public_response<T>(response: &http::Response<String>) -> Result<T, Error>
 where T: DeserializeOwned {
+    util::check_status(response)?;
+    util::require_json(response)?;
     let body = response.body();
-    Ok(serde_json::from_str(body)?)
+    let response = serde_json::from_str(body)
+        .map_err(|e| CcexError::Deserialize(format!("{}: \"{}\"", e, body)))?;
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {MockClient, MockResponse};
+
+    #[test]
+    fn private_requests_are_signed_with_hmac_sha512_of_the_body() {
+        let mut request = http::request::Builder::new()
+            .method(http::Method::POST)
+            .uri("https://api.exmo.com/v1/user_info")
+            .body("nonce=1".to_owned())
+            .unwrap();
+
+        sign_private_request(&mut request, &credential()).unwrap();
+
+        let expected = "1dd023409b0c71a72d21abd20de62d120c6d51234b741a6ce5e13710738e45ed\
+                        f4e29bb291dfe4aadd3cd256ab48b91f68a203aed12a071b5a8b3b2cc8aada67";
+        assert_eq!(request.headers()["Key"], "key");
+        assert_eq!(request.headers()["Sign"], expected);
+        assert_eq!(request.body(), "nonce=1");
+    }
+
+    #[test]
+    fn orderbook_limit_is_sent() {
+        let mut client = MockClient::new("{}");
+        let product: CurrencyPair = "BTC_USD".parse().unwrap();
+        let host = host(Environment::Production);
+
+        let orderbooks = get_orderbooks(&mut client, &host, &[&product], 250).unwrap();
+
+        assert!(orderbooks.is_empty());
+        let query = client.sent[0].uri().query().unwrap().to_owned();
+        assert_eq!(query, "pair=BTC_USD&limit=250");
+    }
+
+    #[test]
+    fn currencies_round_trip() {
+        util::assert_round_trips::<Currency>(&[("btc", "BTC"), ("BTC", "BTC"), ("Usd", "USD")]);
+    }
+
+    #[test]
+    fn currency_pairs_round_trip() {
+        util::assert_round_trips::<CurrencyPair>(&[
+            ("BTC_USD", "BTC_USD"),
+            ("eth_btc", "ETH_BTC"),
+            ("Dash_Rub", "DASH_RUB"),
+        ]);
+    }
+
+    #[test]
+    fn currency_pairs_need_exactly_two_currencies() {
+        for pair in &["BTCUSD", "BTC_USD_EUR", "_USD", "BTC_", ""] {
+            assert!(pair.parse::<CurrencyPair>().is_err(), "parsed {:?}", pair);
+        }
+    }
+
+    #[test]
+    fn signed_body_is_the_body_sent() {
+        let mut client = MockClient::new(
+            r#"{"uid":10542,"server_date":1435518576,"balances":{"BTC":"970.994"},
+            "reserved":{"BTC":"3"}}"#,
+        );
+        let credential = Credential {
+            key: "key".to_owned(),
+            secret: "secret".to_owned(),
+            nonce: 1,
+        };
+        let host = host(Environment::Production);
+
+        let user_info = get_user_info(&mut client, &host, &credential).unwrap();
+
+        assert_eq!(user_info.uid, 10542);
+        let sent = &client.sent[0];
+        assert_eq!(sent.uri().to_string(), "https://api.exmo.com/v1/user_info");
+        assert_eq!(sent.body(), "nonce=1");
+        let signature = HmacSha512Hex.sign("secret", sent.body()).unwrap();
+        assert_eq!(sent.headers()["Key"], "key");
+        assert_eq!(sent.headers()["Sign"], signature);
+    }
+
+    #[test]
+    fn insufficient_funds_is_insufficient_funds() {
+        let response = http::Response::builder()
+            .status(200)
+            .body(r#"{"result":false,"error":"Error 50277: Insufficient funds"}"#.to_owned())
+            .unwrap();
+        let error = deserialize_private_response::<Order>(&response).unwrap_err();
+        match error.downcast_ref::<CcexError>() {
+            Some(&CcexError::InsufficientFunds(ref message)) => {
+                assert_eq!(message, "Error 50277: Insufficient funds")
+            }
+            other => panic!("expected InsufficientFunds, got {:?}", other),
+        }
+
+        match classify_error("Error 50277: Insufficient funds".to_owned()) {
+            CcexError::InsufficientFunds(_) => (),
+            other => panic!("expected InsufficientFunds, got {:?}", other),
+        }
+    }
+
+    const PAIR_SETTINGS: &str = r#"{"min_quantity":"0.001","max_quantity":"100",
+        "min_price":"1","max_price":"30000","max_amount":"500000","min_amount":"1"}"#;
+
+    #[test]
+    fn pair_settings_iterate_in_product_order() {
+        let body = format!(
+            r#"{{"LTC_USD":{0},"BTC_USD":{0},"ETH_BTC":{0},"BTC_EUR":{0}}}"#,
+            PAIR_SETTINGS
+        );
+        let mut client = MockClient::new(&body);
+        let host = host(Environment::Production);
+
+        for _ in 0..2 {
+            let pair_settings = get_pair_settings(&mut client, &host).unwrap();
+            let products: Vec<String> = pair_settings.keys().map(ToString::to_string).collect();
+            assert_eq!(products, vec!["BTC_EUR", "BTC_USD", "ETH_BTC", "LTC_USD"]);
+        }
+    }
+
+    #[test]
+    fn sandbox_is_production() {
+        assert_eq!(host(Environment::Sandbox), host(Environment::Production));
+        assert_eq!(host(Environment::Sandbox).as_str(), API_HOST);
+    }
+
+    fn credential() -> Credential {
+        Credential {
+            key: "key".to_owned(),
+            secret: "secret".to_owned(),
+            nonce: 1,
+        }
+    }
+
+    #[test]
+    fn market_order_below_the_minimum_total_is_never_sent() {
+        let product: CurrencyPair = "BTC_USD".parse().unwrap();
+        let mut pair_settings = BTreeMap::new();
+        pair_settings.insert(product.clone(), serde_json::from_str(PAIR_SETTINGS).unwrap());
+        let mut client = MockClient::queued(vec![]);
+        let host = host(Environment::Production);
+        let size = MarketOrderSize::Total("0.5".parse().unwrap());
+
+        let error = place_validated_market_order(
+            &mut client,
+            &host,
+            &credential(),
+            &pair_settings,
+            &product,
+            size,
+            Side::Buy,
+        ).unwrap_err();
+
+        match error.downcast_ref::<CcexError>() {
+            Some(&CcexError::OrderRejected(ref message)) => {
+                assert_eq!(message, "total 0.5 is below the minimum of 1")
+            }
+            other => panic!("expected OrderRejected, got {:?}", other),
+        }
+        assert!(client.sent.is_empty());
+    }
+
+    #[test]
+    fn market_order_type_depends_on_side_and_size() {
+        let quantity = MarketOrderSize::Quantity("2".parse().unwrap());
+        let total = MarketOrderSize::Total("100".parse().unwrap());
+        let cases = [
+            (Side::Buy, quantity, "market_buy"),
+            (Side::Sell, quantity, "market_sell"),
+            (Side::Buy, total, "market_buy_total"),
+            (Side::Sell, total, "market_sell_total"),
+        ];
+        let product: CurrencyPair = "BTC_USD".parse().unwrap();
+        let host = host(Environment::Production);
+
+        for &(ref side, size, expected) in &cases {
+            let mut client = MockClient::new(r#"{"result":true,"error":"","order_id":123}"#);
+            let side = side.clone();
+            place_market_order(&mut client, &host, &credential(), &product, size, side).unwrap();
+
+            let sent = &client.sent[0];
+            assert_eq!(sent.uri().path(), "/v1/order_create");
+            let instruction = sent.body().split('&').find(|param| param.starts_with("type="));
+            assert_eq!(instruction, Some(format!("type={}", expected).as_str()));
+        }
+    }
+
+    #[test]
+    fn excode_is_created_and_loaded() {
+        let created = r#"{"result":true,"error":"","task_id":"467756",
+            "code":"EX-CODE_9004_BTC_7fa2d5a09d7e4ac26b4f8d6b1e0e7d57","amount":"0.01",
+            "currency":"BTC","balances":{"BTC":"0.0012"}}"#;
+        let loaded = r#"{"result":true,"error":"","task_id":"467757","amount":"0.01",
+            "currency":"BTC"}"#;
+        let mut client = MockClient::queued(vec![
+            MockResponse::new(200, created),
+            MockResponse::new(200, loaded),
+        ]);
+        let host = host(Environment::Production);
+        let btc: Currency = "BTC".parse().unwrap();
+        let amount = "0.01".parse().unwrap();
+
+        let excode = create_excode(&mut client, &host, &credential(), &btc, amount).unwrap();
+        let funds = load_excode(&mut client, &host, &credential(), &excode.code).unwrap();
+
+        assert_eq!(excode.task_id, "467756");
+        assert_eq!(excode.amount, amount);
+        assert_eq!(excode.balances[&btc], "0.0012".parse().unwrap());
+        assert_eq!(client.sent[0].uri().path(), "/v1/excode_create");
+        assert!(client.sent[0].body().contains("currency=BTC"));
+        assert_eq!(funds.task_id, "467757");
+        assert_eq!(funds.currency, btc);
+        assert_eq!(client.sent[1].uri().path(), "/v1/excode_load");
+        let code = format!("code={}", excode.code);
+        assert!(client.sent[1].body().split('&').any(|param| param == code));
+    }
+
+    #[test]
+    fn invalid_excode_is_a_venue_error_with_its_code() {
+        let body = r#"{"result":false,"error":"Error 40005: Incorrect excode"}"#;
+        let mut client = MockClient::new(body);
+        let host = host(Environment::Production);
+
+        let error = load_excode(&mut client, &host, &credential(), "EX-CODE_0").unwrap_err();
+
+        match error.downcast_ref::<CcexError>() {
+            Some(&CcexError::Venue { code: Some(40005), ref message }) => {
+                assert_eq!(message, "Error 40005: Incorrect excode")
+            }
+            other => panic!("expected Venue with code 40005, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn error_without_a_code_is_a_venue_error_without_one() {
+        for message in &["Incorrect excode", "Error: Incorrect excode", "Error abc: Incorrect"] {
+            match classify_error(message.to_string()) {
+                CcexError::Venue { code: None, message: ref quoted } => {
+                    assert_eq!(quoted, message)
+                }
+                other => panic!("expected Venue without a code, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn trades_are_keyed_by_product() {
+        let body = r#"{
+            "BTC_USD":[
+                {"trade_id":3,"type":"sell","price":"100","quantity":"1","amount":"100",
+                "date":1435488248},
+                {"trade_id":4,"type":"buy","price":"101","quantity":"2","amount":"202",
+                "date":1435488249}],
+            "ETH_BTC":[
+                {"trade_id":7,"type":"buy","price":"0.05","quantity":"10","amount":"0.5",
+                "date":1435488250}]}"#;
+        let mut client = MockClient::new(body);
+        let btc_usd: CurrencyPair = "BTC_USD".parse().unwrap();
+        let eth_btc: CurrencyPair = "ETH_BTC".parse().unwrap();
+        let host = host(Environment::Production);
+
+        let trades = get_trades(&mut client, &host, &[&btc_usd, &eth_btc]).unwrap();
+
+        assert_eq!(client.sent[0].uri().query(), Some("pair=BTC_USD,ETH_BTC"));
+        let trade_ids: Vec<u64> = trades[&btc_usd].iter().map(|trade| trade.trade_id).collect();
+        assert_eq!(trade_ids, vec![3, 4]);
+        assert_eq!(trades[&btc_usd][0].side, Side::Sell);
+        assert_eq!(trades[&btc_usd][1].amount, "202".parse().unwrap());
+        let eth_trade = &trades[&eth_btc][0];
+        assert_eq!(eth_trade.trade_id, 7);
+        assert_eq!(eth_trade.side, Side::Buy);
+        assert_eq!(eth_trade.price, "0.05".parse().unwrap());
+        assert_eq!(eth_trade.quantity, "10".parse().unwrap());
+        assert_eq!(eth_trade.date, 1435488250);
+    }
+
+    #[test]
+    fn order_trades_have_the_commission_of_each_fill() {
+        // Shaped like the example in Exmo's API documentation, with a second fill.
+        let body = r#"{"type":"buy","in_currency":"BTC","in_amount":"1.5","out_currency":"USD",
+            "out_amount":"151","trades":[
+                {"trade_id":3,"date":1435488248,"type":"buy","pair":"BTC_USD",
+                "order_id":12345,"quantity":1,"price":100,"amount":100,"exec_type":"taker",
+                "commission_amount":"0.002","commission_currency":"BTC",
+                "commission_percent":"0.2"},
+                {"trade_id":4,"date":1435488249,"type":"buy","pair":"BTC_USD",
+                "order_id":12345,"quantity":"0.5","price":"102","amount":"51","exec_type":"maker",
+                "commission_amount":"0.001","commission_currency":"BTC",
+                "commission_percent":"0.2"}]}"#;
+        let mut client = MockClient::new(body);
+        let host = host(Environment::Production);
+
+        let order_trades = get_order_trades(&mut client, &host, &credential(), 12345).unwrap();
+
+        assert_eq!(client.sent[0].uri().path(), "/v1/order_trades");
+        assert!(client.sent[0].body().split('&').any(|param| param == "order_id=12345"));
+        assert_eq!(order_trades.side, Side::Buy);
+        assert_eq!(order_trades.in_amount, "1.5".parse().unwrap());
+        assert_eq!(order_trades.out_currency, "USD".parse().unwrap());
+        let fills: Vec<(u64, d128, d128, d128)> = order_trades
+            .trades
+            .iter()
+            .map(|trade| (trade.trade_id, trade.price, trade.quantity, trade.commission_amount))
+            .collect();
+        assert_eq!(
+            fills,
+            vec![
+                (3, "100".parse().unwrap(), "1".parse().unwrap(), "0.002".parse().unwrap()),
+                (4, "102".parse().unwrap(), "0.5".parse().unwrap(), "0.001".parse().unwrap()),
+            ]
+        );
+        let btc: Currency = "BTC".parse().unwrap();
+        assert!(order_trades.trades.iter().all(|trade| trade.commission_currency == btc));
+    }
+
+    #[test]
+    fn order_trades_of_an_unknown_order_is_an_error() {
+        let body = r#"{"result":false,"error":"Error 50304: Order was not found"}"#;
+        let mut client = MockClient::new(body);
+        let host = host(Environment::Production);
+
+        let error = get_order_trades(&mut client, &host, &credential(), 12345).unwrap_err();
+
+        match error.downcast_ref::<CcexError>() {
+            Some(&CcexError::Venue { code: Some(50304), .. }) => (),
+            other => panic!("expected Venue with code 50304, got {:?}", other),
+        }
+    }
 }
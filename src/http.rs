@@ -1,6 +1,10 @@
 use failure::Error;
 use http;
 use reqwest;
+#[cfg(test)]
+use std::collections::VecDeque;
+use std::fmt;
+use CcexError;
 
 #[derive(Debug, Default, Clone)]
 pub(crate) struct Query {
@@ -41,6 +45,142 @@ pub trait HttpClient {
     fn send(&mut self, request: &http::Request<String>) -> Result<http::Response<String>, Error>;
 }
 
+/// Wraps an `HttpClient`, handing every request and response to a callback. Useful for debugging
+/// signatures or keeping an audit log of what was sent.
+///
+/// Requests are passed to `on_request` with their credential headers redacted.
+///
+/// ```rust,ignore
+/// let mut client = Inspecting::new(
+///     reqwest::Client::new(),
+///     |request| println!("{} {}", request.method(), request.uri()),
+///     |response| println!("{}", response.status()),
+/// );
+/// binance::get_account_info(&mut client, &host, &credential)?;
+/// ```
+pub struct Inspecting<Client, OnRequest, OnResponse> {
+    pub client: Client,
+    on_request: OnRequest,
+    on_response: OnResponse,
+}
+
+impl<Client, OnRequest, OnResponse> Inspecting<Client, OnRequest, OnResponse>
+where
+    Client: HttpClient,
+    OnRequest: FnMut(&http::Request<String>),
+    OnResponse: FnMut(&http::Response<String>),
+{
+    pub fn new(client: Client, on_request: OnRequest, on_response: OnResponse) -> Self {
+        Inspecting {
+            client,
+            on_request,
+            on_response,
+        }
+    }
+}
+
+impl<Client, OnRequest, OnResponse> HttpClient for Inspecting<Client, OnRequest, OnResponse>
+where
+    Client: HttpClient,
+    OnRequest: FnMut(&http::Request<String>),
+    OnResponse: FnMut(&http::Response<String>),
+{
+    fn send(&mut self, request: &http::Request<String>) -> Result<http::Response<String>, Error> {
+        (self.on_request)(&redacted(request)?);
+        let response = self.client.send(request)?;
+        (self.on_response)(&response);
+        Ok(response)
+    }
+}
+
+impl<Client, OnRequest, OnResponse> fmt::Debug for Inspecting<Client, OnRequest, OnResponse>
+where Client: fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Inspecting")
+            .field("client", &self.client)
+            .finish()
+    }
+}
+
+/// Wraps an `HttpClient`, adding the same headers to every request. Use it to set a `User-Agent`
+/// for exchanges that block the default one.
+///
+/// A header is only added if the request doesn't already have it, so signing headers are never
+/// overwritten.
+///
+/// ```rust,ignore
+/// let mut client = WithHeaders::new(reqwest::Client::new())
+///     .with_header("User-Agent", "my-trading-bot/1.0")?;
+/// ```
+#[derive(Debug)]
+pub struct WithHeaders<Client> {
+    pub client: Client,
+    headers: http::HeaderMap,
+}
+
+impl<Client> WithHeaders<Client>
+where Client: HttpClient
+{
+    pub fn new(client: Client) -> Self {
+        WithHeaders {
+            client,
+            headers: http::HeaderMap::new(),
+        }
+    }
+
+    /// Add `name: value` to every request.
+    pub fn with_header(mut self, name: &str, value: &str) -> Result<Self, Error> {
+        let name: http::header::HeaderName = name.parse()?;
+        let value = http::header::HeaderValue::from_str(value)?;
+        self.headers.insert(name, value);
+        Ok(self)
+    }
+}
+
+impl<Client> HttpClient for WithHeaders<Client>
+where Client: HttpClient
+{
+    fn send(&mut self, request: &http::Request<String>) -> Result<http::Response<String>, Error> {
+        let mut builder = http::request::Builder::new();
+        builder
+            .method(request.method().clone())
+            .uri(request.uri().clone())
+            .version(request.version());
+        for (name, value) in request.headers() {
+            builder.header(name.clone(), value.clone());
+        }
+        for (name, value) in &self.headers {
+            if !request.headers().contains_key(name) {
+                builder.header(name.clone(), value.clone());
+            }
+        }
+        let request = builder.body(request.body().clone())?;
+
+        self.client.send(&request)
+    }
+}
+
+/// Headers that carry an API key or signature.
+const CREDENTIAL_HEADERS: &[&str] = &["key", "sign", "x-mbx-apikey"];
+
+/// A copy of `request` with its credential headers' values replaced.
+fn redacted(request: &http::Request<String>) -> Result<http::Request<String>, Error> {
+    let mut builder = http::request::Builder::new();
+    builder
+        .method(request.method().clone())
+        .uri(request.uri().clone())
+        .version(request.version());
+    for (name, value) in request.headers() {
+        if CREDENTIAL_HEADERS.contains(&name.as_str()) {
+            builder.header(name.as_str(), "<redacted>");
+        } else {
+            builder.header(name.as_str(), value.clone());
+        }
+    }
+    Ok(builder.body(request.body().clone())?)
+}
+
 impl HttpClient for reqwest::Client {
     fn send(&mut self, request: &http::Request<String>) -> Result<http::Response<String>, Error> {
         let method = request.method().as_str().parse()?;
@@ -54,12 +194,188 @@ impl HttpClient for reqwest::Client {
             .headers(headers)
             .build()?;
 
-        let mut response = self.execute(request)?;
+        let mut response = self
+            .execute(request)
+            .map_err(|e| CcexError::Transport(e.to_string()))?;
+
+        let mut builder = http::response::Builder::new();
+        builder.status(response.status().as_u16());
+        for header in response.headers().iter() {
+            builder.header(header.name(), header.value_string().as_str());
+        }
+
+        let body = response
+            .text()
+            .map_err(|e| CcexError::Transport(e.to_string()))?;
+        builder.body(body).map_err(|e| format_err!("{}", e))
+    }
+}
+
+/// An `HttpClient` for tests. Answers requests with queued responses, in order, and keeps a copy
+/// of each request sent.
+#[cfg(test)]
+#[derive(Debug)]
+pub(crate) struct MockClient {
+    /// Responses still to be sent, in order.
+    pub responses: VecDeque<MockResponse>,
+
+    /// Sent once `responses` runs out. Without one, sending more requests than were queued fails.
+    pub fallback: Option<MockResponse>,
+    pub sent: Vec<http::Request<String>>,
+}
+
+#[cfg(test)]
+impl MockClient {
+    /// Answer every request with `200` and `body`.
+    pub fn new(body: &str) -> Self {
+        MockClient {
+            responses: VecDeque::new(),
+            fallback: Some(MockResponse::new(200, body)),
+            sent: Vec::new(),
+        }
+    }
+
+    /// Answer with `responses`, one per request, and fail any request after them.
+    pub fn queued(responses: Vec<MockResponse>) -> Self {
+        MockClient {
+            responses: responses.into_iter().collect(),
+            fallback: None,
+            sent: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl HttpClient for MockClient {
+    fn send(&mut self, request: &http::Request<String>) -> Result<http::Response<String>, Error> {
+        let mut builder = http::request::Builder::new();
+        builder
+            .method(request.method().clone())
+            .uri(request.uri().clone());
+        for (name, value) in request.headers() {
+            builder.header(name.clone(), value.clone());
+        }
+        self.sent.push(builder.body(request.body().clone())?);
+
+        let response = match self.responses.pop_front() {
+            Some(response) => response,
+            None => self
+                .fallback
+                .clone()
+                .ok_or_else(|| format_err!("no response queued for {}", request.uri()))?,
+        };
+        let mut builder = http::response::Builder::new();
+        builder.status(response.status);
+        for &(name, value) in &response.headers {
+            builder.header(name, value);
+        }
+        Ok(builder.body(response.body)?)
+    }
+}
+
+/// A response for `MockClient` to answer with.
+#[cfg(test)]
+#[derive(Debug, Clone)]
+pub(crate) struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(&'static str, &'static str)>,
+    pub body: String,
+}
+
+#[cfg(test)]
+impl MockResponse {
+    pub fn new(status: u16, body: &str) -> Self {
+        MockResponse {
+            status,
+            headers: Vec::new(),
+            body: body.to_owned(),
+        }
+    }
+
+    pub fn with_header(mut self, name: &'static str, value: &'static str) -> Self {
+        self.headers.push((name, value));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signed_request() -> http::Request<String> {
+        http::request::Builder::new()
+            .method(http::Method::POST)
+            .uri("https://api.liqui.io/tapi")
+            .header("Key", "my-key")
+            .header("Sign", "my-signature")
+            .body("method=getInfo&nonce=1".to_owned())
+            .unwrap()
+    }
+
+    #[test]
+    fn inspecting_calls_back_with_redacted_requests_and_responses() {
+        let mut requests = Vec::new();
+        let mut statuses = Vec::new();
+        {
+            let mut client = Inspecting::new(
+                MockClient::new("{}"),
+                |request: &http::Request<String>| {
+                    let key = request.headers()["Key"].to_str().unwrap().to_owned();
+                    let sign = request.headers()["Sign"].to_str().unwrap().to_owned();
+                    requests.push((request.uri().to_string(), key, sign, request.body().clone()));
+                },
+                |response: &http::Response<String>| statuses.push(response.status().as_u16()),
+            );
+            client.send(&signed_request()).unwrap();
+
+            // Only the callback's copy is redacted.
+            assert_eq!(client.client.sent[0].headers()["Key"], "my-key");
+            assert_eq!(client.client.sent[0].headers()["Sign"], "my-signature");
+        }
+
+        let redacted = "<redacted>".to_owned();
+        assert_eq!(
+            requests,
+            vec![(
+                "https://api.liqui.io/tapi".to_owned(),
+                redacted.clone(),
+                redacted,
+                "method=getInfo&nonce=1".to_owned(),
+            )]
+        );
+        assert_eq!(statuses, vec![200]);
+    }
+
+    #[test]
+    fn with_headers_adds_headers_to_every_request() {
+        let mut client = WithHeaders::new(MockClient::new("{}"))
+            .with_header("User-Agent", "my-trading-bot/1.0")
+            .unwrap();
+        client.send(&signed_request()).unwrap();
+        client.send(&signed_request()).unwrap();
+
+        assert_eq!(client.client.sent.len(), 2);
+        for sent in &client.client.sent {
+            assert_eq!(sent.headers()["User-Agent"], "my-trading-bot/1.0");
+            assert_eq!(sent.body(), "method=getInfo&nonce=1");
+        }
+    }
+
+    #[test]
+    fn with_headers_never_overwrites_request_headers() {
+        let mut client = WithHeaders::new(MockClient::new("{}"))
+            .with_header("Key", "someone-elses-key")
+            .unwrap();
+        client.send(&signed_request()).unwrap();
+
+        let sent = &client.client.sent[0];
+        let keys: Vec<_> = sent.headers().get_all("Key").iter().collect();
+        assert_eq!(keys, vec!["my-key"]);
+    }
 
-        // TODO: add headers
-        http::response::Builder::new()
-            .status(response.status().as_u16())
-            .body(response.text()?)
-            .map_err(|e| format_err!("{}", e))
+    #[test]
+    fn with_headers_rejects_invalid_headers() {
+        assert!(WithHeaders::new(MockClient::new("{}")).with_header("Bad Name", "x").is_err());
+        assert!(WithHeaders::new(MockClient::new("{}")).with_header("X-Ok", "bad\nvalue").is_err());
     }
 }
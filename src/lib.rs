@@ -19,9 +19,231 @@ extern crate url;
 
 #[path = "http.rs"]
 mod _http;
-pub use _http::HttpClient;
+pub use _http::{HttpClient, Inspecting, WithHeaders};
 use _http::Query;
+#[cfg(test)]
+use _http::{MockClient, MockResponse};
+
+mod error;
+pub use error::CcexError;
+
+mod signer;
+mod util;
+pub use util::RoundingMode;
 
 pub mod liqui;
+mod liqui_family;
 pub mod binance;
 pub mod exmo;
+
+use failure::Error;
+use serde::de::{self, Deserialize, Deserializer};
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+use url::Url;
+
+/// Which of an exchange's servers to send requests to.
+///
+/// Not every exchange has a sandbox. Those that don't use their production servers for both.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub enum Environment {
+    Production,
+    Sandbox,
+}
+
+/// Base URL of an exchange's REST API, e.g. `https://api.binance.com`.
+///
+/// Each exchange module has a `host(environment)` for its own servers. Use `Host::new` to point
+/// requests somewhere else, like a proxy. Deserializing a `Host` validates it the same way.
+#[derive(Debug, Serialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+pub struct Host(String);
+
+impl Host {
+    /// Parse and normalize `url`. Fails unless it's an absolute `http` or `https` URL without a
+    /// query or fragment. A trailing slash is removed.
+    ///
+    /// ```rust
+    /// # extern crate failure;
+    /// # extern crate ni_ce;
+    /// # use ni_ce::Host;
+    /// # fn main() -> Result<(), failure::Error> {
+    /// let host = Host::new("https://api.binance.com/")?;
+    /// assert_eq!(host.as_str(), "https://api.binance.com");
+    /// assert!(Host::new("api.binance.com").is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new(url: &str) -> Result<Self, Error> {
+        let parsed = Url::parse(url).map_err(|e| format_err!("invalid host {:?}: {}", url, e))?;
+        if parsed.scheme() != "https" && parsed.scheme() != "http" {
+            return Err(format_err!("invalid host {:?}: expected an http(s) URL", url));
+        }
+        if parsed.host_str().is_none() || parsed.query().is_some() || parsed.fragment().is_some() {
+            let message = "expected only a scheme, host, and path";
+            return Err(format_err!("invalid host {:?}: {}", url, message));
+        }
+        Ok(Host(url.trim_right_matches('/').to_owned()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        let &Host(ref host) = self;
+        host
+    }
+}
+
+impl<'de> Deserialize<'de> for Host {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de> {
+        let url = String::deserialize(deserializer)?;
+        Host::new(&url).map_err(de::Error::custom)
+    }
+}
+
+impl Display for Host {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What an API key is allowed to do.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub struct Permissions {
+    /// Read balances, orders, and other account info.
+    pub can_read: bool,
+    pub can_trade: bool,
+    pub can_withdraw: bool,
+}
+
+impl Permissions {
+    /// Fail with `CcexError::Auth` unless the key can trade. Check this before placing orders to
+    /// get a clear error instead of the exchange's rejection.
+    pub fn require_trade(&self) -> Result<(), Error> {
+        if self.can_trade {
+            Ok(())
+        } else {
+            Err(CcexError::Auth("this API key isn't allowed to trade".to_owned()).into())
+        }
+    }
+
+    /// Fail with `CcexError::Auth` unless the key can withdraw.
+    pub fn require_withdraw(&self) -> Result<(), Error> {
+        if self.can_withdraw {
+            Ok(())
+        } else {
+            Err(CcexError::Auth("this API key isn't allowed to withdraw".to_owned()).into())
+        }
+    }
+}
+
+/// **Use this for every withdrawal.** An allowlist of addresses that funds may be withdrawn to.
+///
+/// Withdrawals can't be undone. `withdraw` takes a guard and refuses an address that isn't listed
+/// with `CcexError::AddressNotWhitelisted` before any request is sent, so a bug or a tampered
+/// config can't send funds somewhere unexpected. Skipping the check takes calling
+/// `withdraw_unguarded` by name. Addresses are compared exactly, including case, and any memo or
+/// tag isn't part of the comparison.
+///
+/// ```rust,ignore
+/// let guard = WhitelistGuard::new(vec!["1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2"]);
+/// binance::withdraw(&mut client, &host, &credential, &guard, &btc, amount, address, None)?;
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct WhitelistGuard {
+    addresses: HashSet<String>,
+}
+
+impl WhitelistGuard {
+    pub fn new<I, S>(addresses: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>, {
+        WhitelistGuard {
+            addresses: addresses.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Fail with `CcexError::AddressNotWhitelisted` unless `address` is on the allowlist.
+    pub fn check(&self, address: &str) -> Result<(), Error> {
+        if self.addresses.contains(address) {
+            Ok(())
+        } else {
+            Err(CcexError::AddressNotWhitelisted(address.to_owned()).into())
+        }
+    }
+}
+
+/// What was signed for a private request, for comparing against an exchange's documented example.
+#[cfg(feature = "debug")]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SignatureDebug {
+    /// The exact string that was signed.
+    pub signed: String,
+
+    /// The signature, as sent to the exchange.
+    pub signature: String,
+
+    /// The final query string, for exchanges that send the signature in the query.
+    pub query: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn host_deserializes_through_new() {
+        let host: Host = serde_json::from_str("\"https://api.liqui.io/\"").unwrap();
+        assert_eq!(host.as_str(), "https://api.liqui.io");
+    }
+
+    #[test]
+    fn whitelist_guard_allows_listed_addresses() {
+        let guard = WhitelistGuard::new(vec!["1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2"]);
+        assert!(guard.check("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2").is_ok());
+    }
+
+    #[test]
+    fn whitelist_guard_blocks_other_addresses() {
+        let guard = WhitelistGuard::new(vec!["1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2"]);
+        // Addresses are compared exactly, so a differently cased address is blocked too.
+        let addresses = [
+            "3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy",
+            "1bvbmseystwetqtfn5au4m4gfg7xjanvn2",
+        ];
+        for address in &addresses {
+            let error = guard.check(address).unwrap_err();
+            match error.downcast_ref::<CcexError>() {
+                Some(&CcexError::AddressNotWhitelisted(ref blocked)) => {
+                    assert_eq!(blocked, address)
+                }
+                other => panic!("expected AddressNotWhitelisted, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn host_rejects_invalid_urls_when_deserialized() {
+        assert!(serde_json::from_str::<Host>("\"api.liqui.io\"").is_err());
+        assert!(serde_json::from_str::<Host>("\"ftp://api.liqui.io\"").is_err());
+        assert!(serde_json::from_str::<Host>("\"https://api.liqui.io/?a=b\"").is_err());
+    }
+
+    #[test]
+    fn read_only_permissions_allow_neither_trading_nor_withdrawing() {
+        let permissions = Permissions { can_read: true, can_trade: false, can_withdraw: false };
+        for result in vec![permissions.require_trade(), permissions.require_withdraw()] {
+            match result.unwrap_err().downcast_ref::<CcexError>() {
+                Some(&CcexError::Auth(_)) => (),
+                other => panic!("expected Auth, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn full_permissions_allow_trading_and_withdrawing() {
+        let permissions = Permissions { can_read: true, can_trade: true, can_withdraw: true };
+        assert!(permissions.require_trade().is_ok());
+        assert!(permissions.require_withdraw().is_ok());
+    }
+}
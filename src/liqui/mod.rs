@@ -3,23 +3,36 @@
 //! [Liqui's API documentation](https://liqui.io/api)
 //!
 //! Naming between `ccex::liqui` and Liqui is not 1:1.
-use {HttpClient, Query};
-use failure::{Error, ResultExt};
-use hex;
-use hmac::{Hmac, Mac};
+//!
+//! Exchanges that clone Liqui's API, like WEX, can be used through this module too; pass their
+//! [`Host`] instead of [`host`].
+#[cfg(feature = "debug")]
+use SignatureDebug;
+use {CcexError, Environment, Host, HttpClient, Permissions, Query, RoundingMode};
+use liqui_family::{deserialize_private_response, deserialize_public_response};
+use signer::{HmacSha512Hex, Signer};
+use util::{self, format_amount, format_currency, Case};
+use chrono::{self, DateTime, NaiveDateTime, Utc};
+use failure::Error;
 use rust_decimal::Decimal as d128;
-use serde::de::{self, Deserialize, DeserializeOwned, Deserializer, Visitor};
-use serde;
-use serde_json;
-use sha2::Sha512;
-use std::collections::HashMap;
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::{self, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
 use std::fmt::{self, Display, Formatter};
 use http;
 use std::str::FromStr;
 
-/// Use this as the `host` for REST requests.
+/// Base URL for REST requests. Pass [`host`] to requests rather than this.
 pub const API_HOST: &str = "https://api.liqui.io";
 
+/// The REST host for `environment`. Liqui doesn't have a sandbox, so this is always [`API_HOST`].
+pub fn host(environment: Environment) -> Host {
+    match environment {
+        Environment::Production | Environment::Sandbox => Host(API_HOST.to_owned()),
+    }
+}
+
 /// Credentials needed for private API requests.
 #[derive(Debug, Hash, PartialEq, PartialOrd, Eq, Ord, Clone, Deserialize, Serialize)]
 pub struct Credential {
@@ -39,8 +52,8 @@ pub enum Side {
 impl Display for Side {
     fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
         match *self {
-            Side::Buy => writeln!(f, "buy"),
-            Side::Sell => writeln!(f, "sell"),
+            Side::Buy => f.write_str("buy"),
+            Side::Sell => f.write_str("sell"),
         }
     }
 }
@@ -58,17 +71,20 @@ pub struct Currency(String);
 impl FromStr for Currency {
     type Err = Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Currency(s.to_lowercase()))
+        Ok(Currency(format_currency(s, CURRENCY_CASE)))
     }
 }
 
 impl Display for Currency {
     fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
         let &Currency(ref currency) = self;
-        f.write_str(currency)
+        f.write_str(&format_currency(currency, CURRENCY_CASE))
     }
 }
 
+/// Liqui only accepts currencies like `btc`.
+const CURRENCY_CASE: Case = Case::Lower;
+
 /// Usually represents a product. `ETH_BTC`, `BTC_USDT`, etc.
 #[derive(Debug, Hash, PartialEq, PartialOrd, Eq, Ord, Clone, Serialize)]
 pub struct CurrencyPair(pub Currency, pub Currency);
@@ -95,6 +111,21 @@ impl Display for CurrencyPair {
     }
 }
 
+impl FromStr for CurrencyPair {
+    type Err = Error;
+
+    /// Parse two currencies separated by an underscore, like `eth_btc`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let currencies: Vec<&str> = s.split('_').collect();
+        match currencies.as_slice() {
+            &[base, quote] if !base.is_empty() && !quote.is_empty() => {
+                Ok(CurrencyPair(base.parse()?, quote.parse()?))
+            }
+            _ => Err(format_err!("{:?} isn't two currencies separated by an underscore", s)),
+        }
+    }
+}
+
 impl<'de> Deserialize<'de> for CurrencyPair {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where D: Deserializer<'de> {
@@ -108,13 +139,8 @@ impl<'de> Deserialize<'de> for CurrencyPair {
 
             fn visit_str<E>(self, pair: &str) -> Result<Self::Value, E>
             where E: de::Error {
-                let currencies: Vec<&str> = pair.split('_').collect();
-                if currencies.len() < 2 {
-                    return Err(E::invalid_value(serde::de::Unexpected::Str(pair), &self));
-                }
-                let base = Currency::from_str(currencies[0]).map_err(serde::de::Error::custom)?;
-                let quote = Currency::from_str(currencies[1]).map_err(serde::de::Error::custom)?;
-                Ok(CurrencyPair(base, quote))
+                pair.parse()
+                    .map_err(|_| E::invalid_value(serde::de::Unexpected::Str(pair), &self))
             }
         }
         deserializer.deserialize_str(CurrencyPairVisitor)
@@ -174,6 +200,16 @@ pub struct Rights {
     pub can_withdraw: bool,
 }
 
+impl Rights {
+    pub fn permissions(&self) -> Permissions {
+        Permissions {
+            can_read: self.can_get_info,
+            can_trade: self.can_trade,
+            can_withdraw: self.can_withdraw,
+        }
+    }
+}
+
 /// The result of a newly placed order.
 #[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
 pub struct OrderPlacement {
@@ -238,8 +274,46 @@ pub struct ProductInfo {
     pub taker_fee: d128,
 }
 
+impl ProductInfo {
+    /// Round `price` to the number of decimal places Liqui accepts for this product.
+    pub fn round_price(&self, price: d128, mode: RoundingMode) -> d128 {
+        util::round(price, self.decimal_places, mode)
+    }
+
+    /// Round `amount` to the number of decimal places Liqui accepts for this product.
+    pub fn round_amount(&self, amount: d128, mode: RoundingMode) -> d128 {
+        util::round(amount, self.decimal_places, mode)
+    }
+
+    /// Check `price` and `amount` against the limits of `product`, which this is the info for.
+    ///
+    /// Liqui rejects orders that break these limits with one of its `803..807` error codes, which
+    /// don't say which limit was broken.
+    pub fn validate_order(
+        &self,
+        product: &CurrencyPair,
+        price: d128,
+        amount: d128,
+    ) -> Result<(), Error>
+    {
+        let rejection = if price < self.min_price {
+            format!("price {} is below the minimum price of {}", price, self.min_price)
+        } else if price > self.max_price {
+            format!("price {} is above the maximum price of {}", price, self.max_price)
+        } else if amount < self.min_amount {
+            format!("amount {} is below the minimum amount of {}", amount, self.min_amount)
+        } else {
+            return Ok(());
+        };
+        let rejection = format!("invalid order for {}: {}", product, rejection);
+        Err(CcexError::OrderRejected(rejection).into())
+    }
+}
+
 /// Status of an order.
-#[derive(Debug, Hash, PartialEq, PartialOrd, Eq, Ord, Clone, Copy, Deserialize, Serialize)]
+///
+/// Liqui sends the status as an integer code; use `OrderStatus::try_from` to convert one.
+#[derive(Debug, Hash, PartialEq, PartialOrd, Eq, Ord, Clone, Copy)]
 pub enum OrderStatus {
     Active = 0,
     Executed = 1,
@@ -247,6 +321,57 @@ pub enum OrderStatus {
     CancelledPartiallyExecuted = 3,
 }
 
+impl TryFrom<i32> for OrderStatus {
+    type Error = Error;
+    fn try_from(code: i32) -> Result<Self, Self::Error> {
+        match code {
+            0 => Ok(OrderStatus::Active),
+            1 => Ok(OrderStatus::Executed),
+            2 => Ok(OrderStatus::Cancelled),
+            3 => Ok(OrderStatus::CancelledPartiallyExecuted),
+            code => Err(format_err!("unknown order status code: {}", code)),
+        }
+    }
+}
+
+impl Serialize for OrderStatus {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer {
+        serializer.serialize_i32(*self as i32)
+    }
+}
+
+impl<'de> Deserialize<'de> for OrderStatus {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de> {
+        struct OrderStatusVisitor;
+        impl<'de> Visitor<'de> for OrderStatusVisitor {
+            type Value = OrderStatus;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an integer order status code between 0 and 3")
+            }
+
+            fn visit_i64<E>(self, code: i64) -> Result<Self::Value, E>
+            where E: de::Error {
+                if code < i64::from(i32::min_value()) || code > i64::from(i32::max_value()) {
+                    return Err(E::invalid_value(serde::de::Unexpected::Signed(code), &self));
+                }
+                OrderStatus::try_from(code as i32).map_err(de::Error::custom)
+            }
+
+            fn visit_u64<E>(self, code: u64) -> Result<Self::Value, E>
+            where E: de::Error {
+                if code > i32::max_value() as u64 {
+                    return Err(E::invalid_value(serde::de::Unexpected::Unsigned(code), &self));
+                }
+                OrderStatus::try_from(code as i32).map_err(de::Error::custom)
+            }
+        }
+        deserializer.deserialize_i32(OrderStatusVisitor)
+    }
+}
+
 /// Limit order (the only type of order Liqui supports).
 #[derive(Debug, PartialEq, PartialOrd, Clone, Deserialize, Serialize)]
 pub struct Order {
@@ -260,7 +385,7 @@ pub struct Order {
 }
 
 /// **Public**. Mostly contains product info (min/max price, precision, fees, etc.)
-pub fn get_exchange_info<Client>(client: &mut Client, host: &str) -> Result<ExchangeInfo, Error>
+pub fn get_exchange_info<Client>(client: &mut Client, host: &Host) -> Result<ExchangeInfo, Error>
 where Client: HttpClient {
     let http_request = http::Request::builder()
         .method(http::Method::GET)
@@ -272,10 +397,25 @@ where Client: HttpClient {
     deserialize_public_response(&http_response)
 }
 
+/// **Public**. Liqui's current time, to the second. Liqui has no dedicated endpoint for it, so
+/// this reads `server_time` from [`get_exchange_info`].
+pub fn get_server_time<Client>(client: &mut Client, host: &Host) -> Result<DateTime<Utc>, Error>
+where Client: HttpClient {
+    let server_time = get_exchange_info(client, host)?.server_time;
+    Ok(DateTime::from_utc(NaiveDateTime::from_timestamp(server_time as i64, 0), Utc))
+}
+
+/// **Public**. How far Liqui's clock is ahead of the local clock; negative if it's behind. Only
+/// accurate to about a second, since that's all Liqui reports.
+pub fn clock_skew<Client>(client: &mut Client, host: &Host) -> Result<chrono::Duration, Error>
+where Client: HttpClient {
+    util::clock_skew(|| get_server_time(client, host))
+}
+
 /// **Private**. User account information (balances, api priviliges, and more)
 pub fn get_account_info<Client>(
     client: &mut Client,
-    host: &str,
+    host: &Host,
     credential: &Credential,
 ) -> Result<AccountInfo, Error>
 where
@@ -285,25 +425,25 @@ where
         let mut query = Query::with_capacity(2);
         query.append_param("method", "getInfo");
         query.append_param("nonce", credential.nonce.to_string());
-        query.to_string()
+        query
     };
 
-    let mut http_request = http::request::Builder::new()
-        .method(http::Method::POST)
-        .uri(format!("{}/tapi", host))
-        .body(query)?;
-    sign_private_request(credential, &mut http_request)?;
+    let http_request = util::signed_post(host, "/tapi", query, |request| {
+        sign_private_request(credential, request)
+    })?;
 
     let http_response = client.send(&http_request)?;
     deserialize_private_response(&http_response)
 }
 
 /// **Public**. Market depth.
+///
+/// Books are keyed in product order, so iterating over them is the same on every call.
 pub fn get_orderbooks<Client>(
     client: &mut Client,
-    host: &str,
+    host: &Host,
     products: &[&CurrencyPair],
-) -> Result<HashMap<CurrencyPair, Orderbook>, Error>
+) -> Result<BTreeMap<CurrencyPair, Orderbook>, Error>
 where
     Client: HttpClient,
 {
@@ -319,11 +459,13 @@ where
 }
 
 /// **Public**. Current price/volume ticker.
+///
+/// Tickers are keyed in product order, so iterating over them is the same on every call.
 pub fn get_ticker<Client>(
     client: &mut Client,
-    host: &str,
+    host: &Host,
     products: &[CurrencyPair],
-) -> Result<HashMap<CurrencyPair, Ticker>, Error>
+) -> Result<BTreeMap<CurrencyPair, Ticker>, Error>
 where
     Client: HttpClient,
 {
@@ -338,10 +480,40 @@ where
     deserialize_public_response(&http_response)
 }
 
+/// **Public**. Ticker for every product listed in the exchange info.
+///
+/// Products are requested in chunks so the URI doesn't grow past what Liqui accepts.
+pub fn get_all_tickers<Client>(
+    client: &mut Client,
+    host: &Host,
+) -> Result<BTreeMap<CurrencyPair, Ticker>, Error>
+where
+    Client: HttpClient,
+{
+    let exchange_info = get_exchange_info(client, host)?;
+    let mut products: Vec<CurrencyPair> = exchange_info
+        .products
+        .into_iter()
+        .map(|(product, _)| product)
+        .collect();
+    // Sorted so the same products are requested together on every call.
+    products.sort();
+
+    let mut tickers = BTreeMap::new();
+    for chunk in products.chunks(MAX_PRODUCTS_PER_REQUEST) {
+        tickers.extend(get_ticker(client, host, chunk)?);
+    }
+    Ok(tickers)
+}
+
 /// **Private**. Place a limit order -- the only order type Liqui supports.
+///
+/// Liqui doesn't support client-specified order ids, so there's no way to tell whether a
+/// placement that timed out actually went through. Check [`get_active_orders`] before trying
+/// again, or risk placing the order twice.
 pub fn place_limit_order<Client>(
     client: &mut Client,
-    host: &str,
+    host: &Host,
     credential: &Credential,
     product: &CurrencyPair,
     price: d128,
@@ -351,49 +523,77 @@ pub fn place_limit_order<Client>(
 where
     Client: HttpClient,
 {
-    let body = {
+    let query = {
         let mut query = Query::with_capacity(6);
         query.append_param("nonce", credential.nonce.to_string());
         query.append_param("method", "trade");
         query.append_param("pair", product.to_string());
         query.append_param("type", side.to_string());
-        query.append_param("rate", price.to_string());
-        query.append_param("amount", quantity.to_string());
-        query.to_string()
+        let price_rounding = RoundingMode::for_price(side == Side::Buy);
+        query.append_param("rate", format_amount(price, PRECISION, price_rounding));
+        query.append_param("amount", format_amount(quantity, PRECISION, RoundingMode::Down));
+        query
     };
-    let mut http_request = http::request::Builder::new()
-        .method(http::Method::POST)
-        .uri(format!("{}/tapi", host))
-        .body(body)?;
-    sign_private_request(credential, &mut http_request)?;
+    let http_request = util::signed_post(host, "/tapi", query, |request| {
+        sign_private_request(credential, request)
+    })?;
 
     let http_response = client.send(&http_request)?;
 
     deserialize_private_response(&http_response)
 }
 
+/// **Private**. Place a limit order after rounding and validating it against the product's info.
+///
+/// `price` and `quantity` are rounded to the product's `decimal_places` then checked against its
+/// min/max price and min amount. Buy prices are rounded down, sell prices up, and quantities down,
+/// so rounding never makes the order worse. An order that breaks the product's limits is never
+/// sent.
+pub fn place_validated_limit_order<Client>(
+    client: &mut Client,
+    host: &Host,
+    credential: &Credential,
+    exchange_info: &ExchangeInfo,
+    product: &CurrencyPair,
+    price: d128,
+    quantity: d128,
+    side: Side,
+) -> Result<OrderPlacement, Error>
+where
+    Client: HttpClient,
+{
+    let product_info = exchange_info
+        .products
+        .get(product)
+        .ok_or_else(|| format_err!("{} isn't listed in the exchange info", product))?;
+
+    let price = product_info.round_price(price, RoundingMode::for_price(side == Side::Buy));
+    let quantity = product_info.round_amount(quantity, RoundingMode::Down);
+    product_info.validate_order(product, price, quantity)?;
+
+    place_limit_order(client, host, credential, product, price, quantity, side)
+}
+
 /// **Private**. User's active buy/sell orders for a product.
 pub fn get_active_orders<Client>(
     client: &mut Client,
-    host: &str,
+    host: &Host,
     credential: &Credential,
     product: &CurrencyPair,
 ) -> Result<HashMap<u64, Order>, Error>
 where
     Client: HttpClient,
 {
-    let body = {
+    let query = {
         let mut query = Query::with_capacity(3);
         query.append_param("method", "ActiveOrders");
         query.append_param("nonce", credential.nonce.to_string());
         query.append_param("pair", product.to_string());
-        query.to_string()
+        query
     };
-    let mut http_request = http::request::Builder::new()
-        .method(http::Method::POST)
-        .uri(format!("{}/tapi", host))
-        .body(body)?;
-    sign_private_request(credential, &mut http_request)?;
+    let http_request = util::signed_post(host, "/tapi", query, |request| {
+        sign_private_request(credential, request)
+    })?;
 
     let http_response = client.send(&http_request)?;
 
@@ -403,25 +603,23 @@ where
 /// **Private**. Get a specific order by its Liqui-issued order id.
 pub fn get_order<Client>(
     client: &mut Client,
-    host: &str,
+    host: &Host,
     credential: &Credential,
     order_id: u64,
 ) -> Result<Order, Error>
 where
     Client: HttpClient,
 {
-    let body = {
+    let query = {
         let mut query = Query::with_capacity(3);
         query.append_param("method", "OrderInfo");
         query.append_param("nonce", credential.nonce.to_string());
         query.append_param("order_id", order_id.to_string());
-        query.to_string()
+        query
     };
-    let mut http_request = http::request::Builder::new()
-        .method(http::Method::POST)
-        .uri(format!("{}/tapi", host))
-        .body(body)?;
-    sign_private_request(credential, &mut http_request)?;
+    let http_request = util::signed_post(host, "/tapi", query, |request| {
+        sign_private_request(credential, request)
+    })?;
 
     let http_response = client.send(&http_request)?;
 
@@ -431,157 +629,242 @@ where
 /// **Private**. Cancel an order by its Liqui-issued order id.
 pub fn cancel_order<Client>(
     client: &mut Client,
-    host: &str,
+    host: &Host,
     credential: &Credential,
     order_id: u64,
 ) -> Result<OrderCancellation, Error>
 where
     Client: HttpClient,
 {
-    let body = {
+    let query = {
         let mut query = Query::with_capacity(3);
         query.append_param("method", "CancelOrder");
         query.append_param("nonce", credential.nonce.to_string());
         query.append_param("order_id", order_id.to_string());
-        query.to_string()
+        query
     };
-    let mut http_request = http::request::Builder::new()
-        .method(http::Method::POST)
-        .uri(format!("{}/tapi", host))
-        .body(body)?;
-    sign_private_request(credential, &mut http_request)?;
+    let http_request = util::signed_post(host, "/tapi", query, |request| {
+        sign_private_request(credential, request)
+    })?;
 
     let http_response = client.send(&http_request)?;
 
     deserialize_private_response(&http_response)
 }
 
-/// Response to a private, authenticated request.
-///
-/// As far as I can tell, `PrivateResponse` is ALWAYS returned from the server in all cases.
-#[derive(Debug, Hash, PartialEq, PartialOrd, Eq, Ord, Clone, Deserialize, Serialize)]
-struct PrivateResponse<T> {
-    success: i32,
-    #[serde(rename = "return")]
-    ok: Option<T>,
-    error: Option<String>,
-    code: Option<u32>,
-}
+/// Most products to put in a single public request. Liqui rejects URIs much longer than this
+/// allows.
+const MAX_PRODUCTS_PER_REQUEST: usize = 50;
 
-impl<T> PrivateResponse<T> {
-    pub fn is_ok(&self) -> bool {
-        self.success == 1
-    }
-
-    pub fn into_result(self) -> Result<T, LiquiError> {
-        if self.is_ok() {
-            Ok(self.ok.unwrap())
-        } else {
-            let error = match self.code {
-                Some(code @ 803) | Some(code @ 804) | Some(code @ 805) | Some(code @ 806)
-                | Some(code @ 807) => LiquiError::InvalidOrder(code, self.error.unwrap()),
+/// Liqui never accepts more than 8 decimal places for a price or amount, and no product's
+/// `decimal_places` is more, so rounding to this only drops digits Liqui would reject anyway.
+/// `place_validated_limit_order` rounds to the product's own `decimal_places` first.
+const PRECISION: u32 = 8;
 
-                Some(code @ 831) | Some(code @ 832) => {
-                    LiquiError::InsufficientFunds(code, self.error.unwrap())
-                }
-
-                Some(code @ 833) => LiquiError::OrderNotFound(code, self.error.unwrap()),
-
-                code => LiquiError::Unregistered(code, self.error.unwrap()),
-            };
+/// Run a private request and, if Liqui rejects its nonce, run it once more with a corrected nonce.
+///
+/// The corrected nonce is the one Liqui asks for, or `credential.nonce + 1` if it doesn't say.
+/// Either way `credential.nonce` is left at the nonce that was last sent, so keep incrementing it
+/// as usual afterwards.
+///
+/// ```rust,ignore
+/// let info = liqui::retry_on_invalid_nonce(&mut credential, |credential| {
+///     liqui::get_account_info(&mut client, &host, credential)
+/// })?;
+/// ```
+pub fn retry_on_invalid_nonce<T, F>(credential: &mut Credential, mut request: F) -> Result<T, Error>
+where F: FnMut(&Credential) -> Result<T, Error> {
+    let error = match request(credential) {
+        Ok(response) => return Ok(response),
+        Err(error) => error,
+    };
 
-            Err(error)
-        }
-    }
+    let expected_nonce = match error.downcast_ref::<CcexError>() {
+        Some(&CcexError::InvalidNonce { expected, .. }) => expected,
+        _ => return Err(error),
+    };
+    credential.nonce = expected_nonce.unwrap_or(credential.nonce + 1);
+    request(credential)
 }
 
-#[derive(Debug, Fail)]
-enum LiquiError {
-    #[fail(display = "({}) {}", _0, _1)]
-    InvalidOrder(u32, String),
-
-    #[fail(display = "({}) {}", _0, _1)]
-    InsufficientFunds(u32, String),
+fn sign_private_request(
+    credential: &Credential,
+    request: &mut http::Request<String>,
+) -> Result<(), Error>
+{
+    let signature = private_signature(credential, request.body())?;
 
-    #[fail(display = "({}) {}", _0, _1)]
-    OrderNotFound(u32, String),
+    let headers = request.headers_mut();
+    headers.insert("Key", credential.key.parse().unwrap());
+    headers.insert("Sign", signature.parse().unwrap());
 
-    #[fail(display = "({:?}) {}", _0, _1)]
-    Unregistered(Option<u32>, String),
+    Ok(())
 }
 
-/// Deserialize a response from a *private* REST request.
-fn deserialize_private_response<T>(response: &http::Response<String>) -> Result<T, Error>
-where T: DeserializeOwned {
-    let body = response.body();
-    let response: PrivateResponse<T> = serde_json::from_str(body.as_str())
-        .with_context(|_| format!("failed to deserialize: \"{}\"", body))?;
-
-    response
-        .into_result()
-        .map_err(|e| format_err!("the server returned \"{}\"", e))
-}
+fn private_signature(credential: &Credential, body: &str) -> Result<String, Error> {
+    HmacSha512Hex.sign(&credential.secret, body)
+}
+
+/// The body that's signed for a private request and the signature sent in its `Sign` header.
+/// Compare these against the documented example when a request is rejected for its signature.
+#[cfg(feature = "debug")]
+pub fn debug_signature(credential: &Credential, body: &str) -> Result<SignatureDebug, Error> {
+    Ok(SignatureDebug {
+        signed: body.to_owned(),
+        signature: private_signature(credential, body)?,
+        query: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+    use {MockClient, MockResponse};
+
+    #[test]
+    fn private_requests_are_signed_with_hmac_sha512_of_the_body() {
+        let credential = Credential {
+            key: "key".to_owned(),
+            secret: "secret".to_owned(),
+            nonce: 1,
+        };
+        let mut request = http::request::Builder::new()
+            .method(http::Method::POST)
+            .uri("https://api.liqui.io/tapi")
+            .body("method=getInfo&nonce=1".to_owned())
+            .unwrap();
+
+        sign_private_request(&credential, &mut request).unwrap();
+
+        let expected = "e420ff78eeeb55d09d89f116a88e586082dca58c52af2614c25c4087de562684\
+                        b51949368104fe139ea2831345b7c50a229d04513b59319ceafb00b4b11f5995";
+        assert_eq!(request.headers()["Key"], "key");
+        assert_eq!(request.headers()["Sign"], expected);
+        assert_eq!(request.body(), "method=getInfo&nonce=1");
+    }
 
-/// Response to a public request.
-///
-/// As far as I can tell, a public response is either:
-/// * `T` where `T` is the object being requested, or
-/// * `PublicResponse` in the event of an error.
-#[derive(Deserialize)]
-struct PublicResponse {
-    success: Option<i32>,
-    error: Option<String>,
-}
-
-impl PublicResponse {
-    fn is_ok(&self) -> bool {
-        // If `success` exists it means the response is an error. Also, if `success` exists, it's
-        // always equal to `0`.
-        match self.success {
-            Some(success) => success == 1,
-            None => true,
+    #[test]
+    fn order_status_from_each_code() {
+        let statuses = [
+            (0, OrderStatus::Active),
+            (1, OrderStatus::Executed),
+            (2, OrderStatus::Cancelled),
+            (3, OrderStatus::CancelledPartiallyExecuted),
+        ];
+        for &(code, status) in &statuses {
+            assert_eq!(OrderStatus::try_from(code).unwrap(), status);
+            let json = code.to_string();
+            assert_eq!(serde_json::from_str::<OrderStatus>(&json).unwrap(), status);
+            assert_eq!(serde_json::to_string(&status).unwrap(), json);
         }
     }
 
-    fn error(&self) -> &str {
-        match self.error {
-            Some(ref error) => error.as_str(),
-            None => "",
+    #[test]
+    fn order_status_rejects_unknown_codes() {
+        for &code in &[-1, 4, 42] {
+            assert!(OrderStatus::try_from(code).is_err(), "accepted {}", code);
+            let json = code.to_string();
+            assert!(serde_json::from_str::<OrderStatus>(&json).is_err(), "parsed {}", code);
         }
+        assert!(serde_json::from_str::<OrderStatus>("4294967296").is_err());
+    }
+
+    #[test]
+    fn currencies_round_trip() {
+        util::assert_round_trips::<Currency>(&[("ETH", "eth"), ("eth", "eth"), ("Usdt", "usdt")]);
     }
-}
 
-/// Deserialize a response from a *public* REST request.
-fn deserialize_public_response<T>(response: &http::Response<String>) -> Result<T, Error>
-where T: DeserializeOwned {
-    let body = response.body();
+    #[test]
+    fn currency_pairs_round_trip() {
+        util::assert_round_trips::<CurrencyPair>(&[
+            ("eth_btc", "eth_btc"),
+            ("ETH_BTC", "eth_btc"),
+            ("Ltc_Usdt", "ltc_usdt"),
+        ]);
+    }
 
-    // First, deserialize into `PublicResponse`, to check if the response is an error.
-    let response: PublicResponse = serde_json::from_str(body.as_str())
-        .with_context(|_| format!("failed to deserialize: \"{}\"", body))?;
-    if !response.is_ok() {
-        return Err(format_err!("the server returned: \"{}\"", response.error()));
+    #[test]
+    fn currency_pairs_need_exactly_two_currencies() {
+        for pair in &["ethbtc", "eth_btc_usdt", "_btc", "eth_", ""] {
+            assert!(pair.parse::<CurrencyPair>().is_err(), "parsed {:?}", pair);
+        }
     }
 
-    // Now, deserialize *again* into the expected reponse.
-    let response: T = serde_json::from_str(body.as_str())
-        .with_context(|_| format!("failed to deserialize: \"{}\"", body))?;
-    Ok(response)
-}
+    #[test]
+    fn invalid_nonce_is_retried_once_with_the_nonce_liqui_asks_for() {
+        let mut client = MockClient::queued(vec![
+            MockResponse::new(
+                200,
+                r#"{"success":0,
+                "error":"invalid nonce parameter; on key:4, you sent:'1', you should send:5"}"#,
+            ),
+            MockResponse::new(
+                200,
+                r#"{"success":1,"return":{"order_id":343154,"funds":{"eth":2,"btc":0.5}}}"#,
+            ),
+        ]);
+        let host = host(Environment::Production);
+        let mut credential = Credential {
+            secret: "secret".to_owned(),
+            key: "key".to_owned(),
+            nonce: 1,
+        };
+
+        let cancellation = retry_on_invalid_nonce(&mut credential, |credential| {
+            cancel_order(&mut client, &host, credential, 343154)
+        }).unwrap();
+
+        assert_eq!(cancellation.order_id, 343154);
+        assert_eq!(client.sent.len(), 2);
+        assert_eq!(client.sent[0].body(), "method=CancelOrder&nonce=1&order_id=343154");
+        assert_eq!(client.sent[1].body(), "method=CancelOrder&nonce=5&order_id=343154");
+        assert_eq!(credential.nonce, 5);
+    }
 
-fn sign_private_request(
-    credential: &Credential,
-    request: &mut http::Request<String>,
-) -> Result<(), Error>
-{
-    let mut mac =
-        Hmac::<Sha512>::new(credential.secret.as_bytes()).map_err(|e| format_err!("{:?}", e))?;
-    mac.input(request.body().as_bytes());
-    let signature = hex::encode(mac.result().code().to_vec());
+    /// A JSON object with `value` for each of `products`.
+    fn keyed_by_product(products: &[String], value: &str) -> String {
+        let entries: Vec<String> = products
+            .iter()
+            .map(|product| format!("\"{}\":{}", product, value))
+            .collect();
+        format!("{{{}}}", entries.join(","))
+    }
 
-    let headers = request.headers_mut();
-    headers.insert("Key", credential.key.parse().unwrap());
-    headers.insert("Sign", signature.parse().unwrap());
+    #[test]
+    fn all_tickers_are_requested_in_chunks_and_merged() {
+        // One more product than fits in a single request.
+        let products: Vec<String> = (0..MAX_PRODUCTS_PER_REQUEST + 1)
+            .map(|i| format!("c{:02}_btc", i))
+            .collect();
+        let product_info = r#"{"decimal_places":8,"min_price":0.00000001,"max_price":1000,
+            "min_amount":0.01,"hidden":0,"fee":0.25}"#;
+        let ticker = r#"{"high":2,"low":1,"avg":1.5,"vol":10,"vol_cur":15,"last":1.5,"buy":1.4,
+            "sell":1.6,"updated":1519920000}"#;
+        let exchange_info = format!(
+            r#"{{"server_time":1519920000,"pairs":{}}}"#,
+            keyed_by_product(&products, product_info)
+        );
+        let (first, second) = products.split_at(MAX_PRODUCTS_PER_REQUEST);
+        let mut client = MockClient::queued(vec![
+            MockResponse::new(200, &exchange_info),
+            MockResponse::new(200, &keyed_by_product(first, ticker)),
+            MockResponse::new(200, &keyed_by_product(second, ticker)),
+        ]);
+
+        let tickers = get_all_tickers(&mut client, &host(Environment::Production)).unwrap();
+
+        let pairs: Vec<String> = tickers.keys().map(ToString::to_string).collect();
+        assert_eq!(pairs, products);
+        assert_eq!(client.sent.len(), 3);
+        assert_eq!(client.sent[0].uri().path(), "/api/3/info");
+        assert_eq!(client.sent[1].uri().path(), format!("/api/3/ticker/{}", first.join("-")));
+        assert_eq!(client.sent[2].uri().path(), format!("/api/3/ticker/{}", second.join("-")));
+    }
 
-    Ok(())
+    #[test]
+    fn sandbox_is_production() {
+        assert_eq!(host(Environment::Sandbox), host(Environment::Production));
+        assert_eq!(host(Environment::Sandbox).as_str(), API_HOST);
+    }
 }
@@ -0,0 +1,303 @@
+//! Response handling shared by Liqui and the exchanges that copied its API.
+//!
+//! Several exchanges (WEX among them) serve Liqui's API as-is: the same methods, the same
+//! `{success, return, error, code}` envelope, and the same `Key`/`Sign` headers. Since
+//! [`liqui::Currency`](../liqui/struct.Currency.html) isn't limited to Liqui's listings, a clone
+//! only needs its own [`Host`](../struct.Host.html) serving `/api/3` and `/tapi`:
+//!
+//! ```rust,ignore
+//! let host = Host::new("https://wex.nz")?;
+//! let tickers = liqui::get_ticker(&mut client, &host, &products)?;
+//! ```
+//!
+//! Clones that change the envelope should deserialize through here so every Liqui-family exchange
+//! reports errors the same way.
+use CcexError;
+use error::is_insufficient_funds;
+use util;
+use failure::Error;
+use http;
+use serde::de::DeserializeOwned;
+use serde_json;
+
+/// An error returned by Liqui, or one of its clones, in response to a private request.
+///
+/// Only used to classify the response; requests return it converted to a `CcexError`.
+#[derive(Debug, Fail)]
+pub(crate) enum LiquiError {
+    #[fail(display = "({}) {}", _0, _1)]
+    InvalidOrder(u32, String),
+
+    /// The request's nonce was reused or lower than the last nonce Liqui saw for the key.
+    #[fail(display = "({:?}) {}", _0, _1)]
+    InvalidNonce(Option<u32>, String),
+
+    #[fail(display = "({}) {}", _0, _1)]
+    InsufficientFunds(u32, String),
+
+    #[fail(display = "({}) {}", _0, _1)]
+    OrderNotFound(u32, String),
+
+    #[fail(display = "({:?}) {}", _0, _1)]
+    Unregistered(Option<u32>, String),
+
+    /// The request succeeded but the response had no `return` payload.
+    #[fail(display = "unexpected empty payload: \"return\" was null or missing")]
+    EmptyReturn,
+}
+
+impl LiquiError {
+    /// The nonce Liqui expects next, if this is an `InvalidNonce` error.
+    ///
+    /// Liqui reports it in the error message, e.g. `"invalid nonce parameter; on key:4, you
+    /// sent:'1', you should send:5"`.
+    fn expected_nonce(&self) -> Option<u64> {
+        match *self {
+            LiquiError::InvalidNonce(_, ref message) => {
+                let (_, expected) = message.split_at(message.rfind(':')? + 1);
+                expected.trim().parse().ok()
+            }
+            _ => None,
+        }
+    }
+}
+
+impl From<LiquiError> for CcexError {
+    fn from(error: LiquiError) -> Self {
+        let expected = error.expected_nonce();
+        match error {
+            LiquiError::InvalidOrder(_, message) => CcexError::OrderRejected(message),
+            LiquiError::InvalidNonce(_, message) => CcexError::InvalidNonce { expected, message },
+            LiquiError::InsufficientFunds(_, message) => CcexError::InsufficientFunds(message),
+            LiquiError::OrderNotFound(code, message) => {
+                CcexError::Venue { code: Some(i64::from(code)), message }
+            }
+            LiquiError::Unregistered(_, ref message) if is_insufficient_funds(message) => {
+                CcexError::InsufficientFunds(message.clone())
+            }
+            LiquiError::Unregistered(_, ref message)
+                if message.contains("invalid sign") || message.contains("api key") =>
+            {
+                CcexError::Auth(message.clone())
+            }
+            LiquiError::Unregistered(code, message) => {
+                CcexError::Venue { code: code.map(i64::from), message }
+            }
+            LiquiError::EmptyReturn => CcexError::Deserialize(LiquiError::EmptyReturn.to_string()),
+        }
+    }
+}
+
+/// Response to a private, authenticated request.
+///
+/// As far as I can tell, `PrivateResponse` is ALWAYS returned from the server in all cases.
+#[derive(Debug, Hash, PartialEq, PartialOrd, Eq, Ord, Clone, Deserialize, Serialize)]
+pub(crate) struct PrivateResponse<T> {
+    success: i32,
+    #[serde(rename = "return")]
+    ok: Option<T>,
+    error: Option<String>,
+    code: Option<u32>,
+}
+
+impl<T> PrivateResponse<T> {
+    pub fn is_ok(&self) -> bool {
+        self.success == 1
+    }
+
+    pub fn into_result(self) -> Result<T, LiquiError> {
+        if self.is_ok() {
+            self.ok.ok_or(LiquiError::EmptyReturn)
+        } else {
+            let message = self.error.unwrap_or_default();
+            if message.contains("invalid nonce") {
+                return Err(LiquiError::InvalidNonce(self.code, message));
+            }
+
+            let error = match self.code {
+                Some(code @ 803) | Some(code @ 804) | Some(code @ 805) | Some(code @ 806)
+                | Some(code @ 807) => LiquiError::InvalidOrder(code, message),
+
+                Some(code @ 831) | Some(code @ 832) => {
+                    LiquiError::InsufficientFunds(code, message)
+                }
+
+                Some(code @ 833) => LiquiError::OrderNotFound(code, message),
+
+                code => LiquiError::Unregistered(code, message),
+            };
+
+            Err(error)
+        }
+    }
+}
+
+/// Deserialize a response from a *private* REST request.
+pub(crate) fn deserialize_private_response<T>(
+    response: &http::Response<String>,
+) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+{
+    util::check_status(response)?;
+    util::require_json(response)?;
+    let body = response.body();
+    let response: PrivateResponse<T> = serde_json::from_str(body.as_str())
+        .map_err(|e| CcexError::Deserialize(format!("{}: \"{}\"", e, body)))?;
+
+    response
+        .into_result()
+        .map_err(|e| Error::from(CcexError::from(e)))
+}
+
+/// Response to a public request.
+///
+/// As far as I can tell, a public response is either:
+/// * `T` where `T` is the object being requested, or
+/// * `PublicResponse` in the event of an error.
+#[derive(Deserialize)]
+pub(crate) struct PublicResponse {
+    success: Option<i32>,
+    error: Option<String>,
+}
+
+impl PublicResponse {
+    fn is_ok(&self) -> bool {
+        // If `success` exists it means the response is an error. Also, if `success` exists, it's
+        // always equal to `0`.
+        match self.success {
+            Some(success) => success == 1,
+            None => true,
+        }
+    }
+
+    fn error(&self) -> &str {
+        match self.error {
+            Some(ref error) => error.as_str(),
+            None => "",
+        }
+    }
+}
+
+/// Deserialize a response from a *public* REST request.
+pub(crate) fn deserialize_public_response<T>(
+    response: &http::Response<String>,
+) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+{
+    util::check_status(response)?;
+    util::require_json(response)?;
+    let body = response.body();
+
+    // First, deserialize into `PublicResponse`, to check if the response is an error.
+    let response: PublicResponse = serde_json::from_str(body.as_str())
+        .map_err(|e| CcexError::Deserialize(format!("{}: \"{}\"", e, body)))?;
+    if !response.is_ok() {
+        let message = response.error().to_owned();
+        return Err(CcexError::Venue { code: None, message }.into());
+    }
+
+    // Now, deserialize *again* into the expected reponse.
+    let response: T = serde_json::from_str(body.as_str())
+        .map_err(|e| CcexError::Deserialize(format!("{}: \"{}\"", e, body)))?;
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use liqui::{Currency, CurrencyPair, OrderCancellation, Ticker};
+    use std::collections::BTreeMap;
+    use Host;
+
+    fn response(body: &str) -> http::Response<String> {
+        http::Response::builder()
+            .status(200)
+            .body(body.to_owned())
+            .unwrap()
+    }
+
+    /// A clone listing currencies Liqui never did, served from its own host.
+    #[test]
+    fn clone_with_its_own_currencies() {
+        let host = Host::new("https://clone.example/").unwrap();
+        assert_eq!(host.as_str(), "https://clone.example");
+
+        let tickers = response(
+            r#"{"dsh_rur":{"high":9500,"low":9000,"avg":9250,"vol":1200.5,"vol_cur":0.13,
+            "last":9400,"buy":9390,"sell":9410,"updated":1519920000}}"#,
+        );
+        let tickers: BTreeMap<CurrencyPair, Ticker> =
+            deserialize_public_response(&tickers).unwrap();
+        let product = CurrencyPair("dsh".parse().unwrap(), "rur".parse().unwrap());
+        assert_eq!(tickers[&product].last, "9400".parse().unwrap());
+
+        let cancellation = response(
+            r#"{"success":1,"return":{"order_id":343154,"funds":{"dsh":2,"rur":1250.5}}}"#,
+        );
+        let cancellation: OrderCancellation =
+            deserialize_private_response(&cancellation).unwrap();
+        assert_eq!(cancellation.order_id, 343154);
+        let rur: Currency = "RUR".parse().unwrap();
+        assert_eq!(cancellation.funds[&rur], "1250.5".parse().unwrap());
+    }
+
+    #[test]
+    fn success_without_return_is_an_error() {
+        for body in &[r#"{"success":1}"#, r#"{"success":1,"return":null}"#] {
+            let error = deserialize_private_response::<OrderCancellation>(&response(body))
+                .unwrap_err();
+            match error.downcast_ref::<CcexError>() {
+                Some(&CcexError::Deserialize(ref message)) => {
+                    assert!(message.contains("unexpected empty payload"))
+                }
+                other => panic!("expected Deserialize, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn clone_errors_are_classified() {
+        let error = response(
+            r#"{"success":0,"error":"It is not enough RUR for purchase","code":831}"#,
+        );
+        let error = deserialize_private_response::<OrderCancellation>(&error).unwrap_err();
+        match error.downcast_ref::<CcexError>() {
+            Some(&CcexError::InsufficientFunds(_)) => (),
+            other => panic!("expected InsufficientFunds, got {:?}", other),
+        }
+
+        let error = response(r#"{"success":0,"error":"Invalid pair name: abc_rur"}"#);
+        let error = deserialize_public_response::<BTreeMap<CurrencyPair, Ticker>>(&error);
+        assert!(error.is_err());
+    }
+
+    /// Assert that `response` fails with a `Venue` error quoting `expected` from its body.
+    fn assert_venue_error_quotes(response: &http::Response<String>, expected: &str) {
+        let error = deserialize_public_response::<BTreeMap<CurrencyPair, Ticker>>(response)
+            .unwrap_err();
+        match error.downcast_ref::<CcexError>() {
+            Some(&CcexError::Venue { code: None, ref message }) => {
+                assert!(message.contains(expected), "{:?} doesn't quote the body", message);
+                assert!(!message.contains("expected value"), "{:?} is a serde error", message);
+            }
+            other => panic!("expected Venue, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn maintenance_page_is_reported_with_a_snippet() {
+        assert_venue_error_quotes(&response(util::MAINTENANCE_PAGE), "down for maintenance");
+    }
+
+    #[test]
+    fn cloudflare_error_is_reported_with_its_status_and_a_snippet() {
+        let response = http::Response::builder()
+            .status(502)
+            .body(util::CLOUDFLARE_PAGE.to_owned())
+            .unwrap();
+        assert_venue_error_quotes(&response, "cloudflare");
+        assert_venue_error_quotes(&response, "status 502");
+    }
+}
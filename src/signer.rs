@@ -0,0 +1,76 @@
+use failure::Error;
+use hex;
+use hmac::{Hmac, Mac};
+use sha2::{Sha256, Sha512};
+
+/// A way of signing private requests with an API secret. Exchanges differ in the hash and
+/// encoding; what gets signed (query or body) is up to the caller.
+pub(crate) trait Signer {
+    fn sign(&self, secret: &str, message: &str) -> Result<String, Error>;
+}
+
+/// HMAC-SHA256, hex-encoded. Used by Binance.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct HmacSha256Hex;
+
+impl Signer for HmacSha256Hex {
+    fn sign(&self, secret: &str, message: &str) -> Result<String, Error> {
+        let mut mac = Hmac::<Sha256>::new(secret.as_bytes()).map_err(|e| format_err!("{:?}", e))?;
+        mac.input(message.as_bytes());
+        Ok(hex::encode(mac.result().code().to_vec()))
+    }
+}
+
+/// HMAC-SHA512, hex-encoded. Used by Liqui and Exmo.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct HmacSha512Hex;
+
+impl Signer for HmacSha512Hex {
+    fn sign(&self, secret: &str, message: &str) -> Result<String, Error> {
+        let mut mac = Hmac::<Sha512>::new(secret.as_bytes()).map_err(|e| format_err!("{:?}", e))?;
+        mac.input(message.as_bytes());
+        Ok(hex::encode(mac.result().code().to_vec()))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// From Binance's REST API documentation, "SIGNED Endpoint Examples". Shared with the
+    /// `binance` tests.
+    pub(crate) const BINANCE_SECRET: &str =
+        "NhqPtmdSJYdKjVHjA7PZj4Mge3R5YNiP1e3UZjInClVN65XAbvqqM6A7H5fATj0j";
+    pub(crate) const BINANCE_QUERY: &str =
+        "symbol=LTCBTC&side=BUY&type=LIMIT&timeInForce=GTC&quantity=1&price=0.1&\
+         recvWindow=5000&timestamp=1499827319559";
+    pub(crate) const BINANCE_SIGNATURE: &str =
+        "c8db56825ae71d6d79447849e617115f4a920fa2acdcab2b053c4b2838bd6b71";
+
+    /// RFC 4231, test case 2. Liqui and Exmo document the algorithm but not a worked example.
+    const RFC_4231_KEY: &str = "Jefe";
+    const RFC_4231_DATA: &str = "what do ya want for nothing?";
+    const RFC_4231_SHA256: &str =
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843";
+    const RFC_4231_SHA512: &str =
+        "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea250554\
+         9758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737";
+
+    #[test]
+    fn hmac_sha256_hex_matches_binance_example() {
+        let signature = HmacSha256Hex.sign(BINANCE_SECRET, BINANCE_QUERY).unwrap();
+        assert_eq!(signature, BINANCE_SIGNATURE);
+    }
+
+    #[test]
+    fn hmac_sha256_hex_matches_rfc_4231() {
+        let signature = HmacSha256Hex.sign(RFC_4231_KEY, RFC_4231_DATA).unwrap();
+        assert_eq!(signature, RFC_4231_SHA256);
+    }
+
+    #[test]
+    fn hmac_sha512_hex_matches_rfc_4231() {
+        let signature = HmacSha512Hex.sign(RFC_4231_KEY, RFC_4231_DATA).unwrap();
+        assert_eq!(signature, RFC_4231_SHA512);
+    }
+}
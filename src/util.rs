@@ -0,0 +1,312 @@
+use chrono::{self, DateTime, Utc};
+use failure::Error;
+use http;
+use num_traits::Zero;
+use rust_decimal::Decimal as d128;
+use std::time::Duration;
+use {CcexError, Host, Query};
+
+/// How to round a decimal that has more decimal places than an exchange accepts.
+///
+/// There's no separate side-aware "toward safe" mode, since the safe direction depends on what's
+/// being rounded. Use [`RoundingMode::for_price`] for prices and `Down` for quantities; every
+/// order function in this crate does.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub enum RoundingMode {
+    /// Toward zero.
+    Down,
+
+    /// Away from zero.
+    Up,
+
+    /// To the nearest value, with ties going to the even neighbour (banker's rounding).
+    HalfEven,
+}
+
+impl RoundingMode {
+    /// The rounding mode that never makes an order's price worse: buy prices are rounded down and
+    /// sell prices are rounded up.
+    ///
+    /// Quantities are always safest rounded `Down`, so the order never needs more funds than
+    /// intended.
+    pub fn for_price(buying: bool) -> Self {
+        if buying {
+            RoundingMode::Down
+        } else {
+            RoundingMode::Up
+        }
+    }
+}
+
+/// Round `value` to `decimal_places` using `mode`.
+///
+/// ```rust,ignore
+/// // At a 0.01 tick:
+/// assert_eq!(round("1.005".parse()?, 2, RoundingMode::Down), "1.00".parse()?);
+/// assert_eq!(round("1.001".parse()?, 2, RoundingMode::Up), "1.01".parse()?);
+/// assert_eq!(round("1.005".parse()?, 2, RoundingMode::HalfEven), "1.00".parse()?);
+/// assert_eq!(round("1.015".parse()?, 2, RoundingMode::HalfEven), "1.02".parse()?);
+/// ```
+pub(crate) fn round(value: d128, decimal_places: u32, mode: RoundingMode) -> d128 {
+    let nearest = value.round_dp(decimal_places);
+    let step = d128::new(1, decimal_places);
+    let negative = value < d128::zero();
+
+    match mode {
+        RoundingMode::HalfEven => nearest,
+        RoundingMode::Down if !negative && nearest > value => nearest - step,
+        RoundingMode::Down if negative && nearest < value => nearest + step,
+        RoundingMode::Up if !negative && nearest < value => nearest + step,
+        RoundingMode::Up if negative && nearest > value => nearest - step,
+        RoundingMode::Down | RoundingMode::Up => nearest,
+    }
+}
+
+/// Format `value` with at most `precision` decimal places, for use in request parameters.
+///
+/// Extra decimal places are rounded away with `mode`. Trailing zeros are stripped, and the result
+/// is never in scientific notation.
+///
+/// ```rust,ignore
+/// assert_eq!(format_amount("0.000010000".parse()?, 8, RoundingMode::Down), "0.00001");
+/// assert_eq!(format_amount("1.123456789".parse()?, 8, RoundingMode::Down), "1.12345678");
+/// assert_eq!(format_amount("1500.00".parse()?, 8, RoundingMode::Down), "1500");
+/// ```
+pub(crate) fn format_amount(value: d128, precision: u32, mode: RoundingMode) -> String {
+    let value = round(value, precision, mode).to_string();
+    let formatted = if value.contains('.') {
+        value.trim_right_matches('0').trim_right_matches('.')
+    } else {
+        value.as_str()
+    };
+
+    // Rounding a small negative value toward zero leaves a meaningless "-0".
+    if formatted == "-0" {
+        "0".to_owned()
+    } else {
+        formatted.to_owned()
+    }
+}
+
+/// Fail with a readable error if `response`'s body isn't JSON, e.g. an HTML maintenance page, a
+/// Cloudflare challenge, or a compressed body that wasn't decoded, instead of leaving it to serde's
+/// "expected value at line 1".
+pub(crate) fn require_json(response: &http::Response<String>) -> Result<(), CcexError> {
+    let body = response.body().trim_left();
+    let encoding = response
+        .headers()
+        .get("Content-Encoding")
+        .and_then(|value| value.to_str().ok())
+        .filter(|encoding| *encoding != "identity");
+    if body.starts_with('{') || body.starts_with('[') {
+        Ok(())
+    } else if let Some(encoding) = encoding {
+        // The `HttpClient` is expected to decode bodies; reqwest does unless gzip is disabled.
+        let message = format!(
+            "status {}: body is still {}-encoded; the HttpClient must decode it",
+            response.status(),
+            encoding
+        );
+        Err(CcexError::Transport(message))
+    } else {
+        let message = format!(
+            "status {}: expected JSON but got \"{}\"",
+            response.status(),
+            snippet(body)
+        );
+        Err(CcexError::Venue { code: None, message })
+    }
+}
+
+/// Fail if `response`'s status says the request wasn't handled. For exchanges that report their
+/// errors in the body of a `200`, so a rate limit or an outage isn't mistaken for a bad body.
+pub(crate) fn check_status(response: &http::Response<String>) -> Result<(), CcexError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+
+    let message = format!("status {}: \"{}\"", status, snippet(response.body()));
+    match status.as_u16() {
+        429 | 418 => Err(CcexError::RateLimited { retry_after: retry_after(response) }),
+        401 | 403 => Err(CcexError::Auth(message)),
+        _ => Err(CcexError::Venue { code: None, message }),
+    }
+}
+
+/// How long `response`'s `Retry-After` header asks to wait, if it's given in seconds.
+pub(crate) fn retry_after(response: &http::Response<String>) -> Option<Duration> {
+    response
+        .headers()
+        .get("Retry-After")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .map(Duration::from_secs)
+}
+
+/// The start of `body`, short enough to put in an error message.
+pub(crate) fn snippet(body: &str) -> String {
+    const MAX_CHARS: usize = 200;
+    if body.chars().count() > MAX_CHARS {
+        let mut snippet: String = body.chars().take(MAX_CHARS).collect();
+        snippet.push_str("...");
+        snippet
+    } else {
+        body.to_owned()
+    }
+}
+
+/// How far the time returned by `server_time` is ahead of the local clock; negative if it's
+/// behind.
+pub(crate) fn clock_skew<F>(server_time: F) -> Result<chrono::Duration, Error>
+where F: FnOnce() -> Result<DateTime<Utc>, Error> {
+    let sent = Utc::now();
+    let server_time = server_time()?;
+    let received = Utc::now();
+
+    // Assume the server read its clock halfway through the round trip.
+    let local_time = sent + received.signed_duration_since(sent) / 2;
+    Ok(server_time.signed_duration_since(local_time))
+}
+
+/// Build a POST request to `path` on `host` with `query` as its body, and sign it with `sign`.
+/// Exchanges that sign the request body go through this so a private request can't be sent
+/// unsigned, and the body that's sent is always the one that was signed.
+pub(crate) fn signed_post<F>(
+    host: &Host,
+    path: &str,
+    query: Query,
+    sign: F,
+) -> Result<http::Request<String>, Error>
+where
+    F: FnOnce(&mut http::Request<String>) -> Result<(), Error>,
+{
+    let mut request = http::request::Builder::new()
+        .method(http::Method::POST)
+        .uri(format!("{}{}", host, path))
+        .body(query.to_string())?;
+    sign(&mut request)?;
+    Ok(request)
+}
+
+/// Letter case an exchange expects currencies in.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum Case {
+    Lower,
+    Upper,
+}
+
+/// `currency` in the case an exchange expects, e.g. `btc` for Liqui and `BTC` for Binance.
+pub(crate) fn format_currency(currency: &str, case: Case) -> String {
+    match case {
+        Case::Lower => currency.to_lowercase(),
+        Case::Upper => currency.to_uppercase(),
+    }
+}
+
+/// Assert that each `(input, expected)` parses and displays as `expected`, and that `expected`
+/// parses back to the same value.
+#[cfg(test)]
+pub(crate) fn assert_round_trips<T>(cases: &[(&str, &str)])
+where
+    T: ::std::str::FromStr + ::std::fmt::Display + ::std::fmt::Debug + PartialEq,
+    T::Err: ::std::fmt::Debug,
+{
+    for &(input, expected) in cases {
+        let parsed: T = input.parse().unwrap();
+        assert_eq!(parsed.to_string(), expected, "displaying {:?}", input);
+        assert_eq!(expected.parse::<T>().unwrap(), parsed, "reparsing {:?}", expected);
+    }
+}
+
+/// A maintenance page served in place of an API response.
+#[cfg(test)]
+pub(crate) const MAINTENANCE_PAGE: &str = "<!DOCTYPE html>\n<html><head><title>Maintenance</title>\
+                                           </head><body><h1>We are down for maintenance</h1>\
+                                           </body></html>";
+
+/// A Cloudflare error page, sent with a `5xx` status when the exchange's servers don't answer.
+#[cfg(test)]
+pub(crate) const CLOUDFLARE_PAGE: &str = "<html>\n<head><title>502 Bad Gateway</title></head>\n\
+                                          <body><center><h1>502 Bad Gateway</h1></center>\n\
+                                          <hr><center>cloudflare</center></body></html>";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn d(value: &str) -> d128 {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn round_at_a_tick() {
+        assert_eq!(round(d("1.005"), 2, RoundingMode::Down), d("1.00"));
+        assert_eq!(round(d("1.001"), 2, RoundingMode::Up), d("1.01"));
+        assert_eq!(round(d("1.005"), 2, RoundingMode::HalfEven), d("1.00"));
+        assert_eq!(round(d("1.015"), 2, RoundingMode::HalfEven), d("1.02"));
+        assert_eq!(round(d("1.009"), 2, RoundingMode::Down), d("1.00"));
+    }
+
+    #[test]
+    fn round_leaves_exact_values_alone() {
+        for &mode in &[RoundingMode::Down, RoundingMode::Up, RoundingMode::HalfEven] {
+            assert_eq!(round(d("1.01"), 2, mode), d("1.01"));
+            assert_eq!(round(d("7"), 2, mode), d("7"));
+        }
+    }
+
+    #[test]
+    fn round_negative_values_relative_to_zero() {
+        assert_eq!(round(d("-1.009"), 2, RoundingMode::Down), d("-1.00"));
+        assert_eq!(round(d("-1.001"), 2, RoundingMode::Up), d("-1.01"));
+    }
+
+    #[test]
+    fn price_rounding_never_worsens_the_order() {
+        assert_eq!(RoundingMode::for_price(true), RoundingMode::Down);
+        assert_eq!(RoundingMode::for_price(false), RoundingMode::Up);
+    }
+
+    #[test]
+    fn safe_rounding_at_a_tick() {
+        let price = d("100.005");
+        assert_eq!(format_amount(price, 2, RoundingMode::for_price(true)), "100");
+        assert_eq!(format_amount(price, 2, RoundingMode::for_price(false)), "100.01");
+        assert_eq!(format_amount(d("1.239"), 2, RoundingMode::Down), "1.23");
+        assert_eq!(format_amount(price, 2, RoundingMode::HalfEven), "100");
+    }
+
+    #[test]
+    fn format_amount_limits_precision() {
+        assert_eq!(format_amount(d("0.000010000"), 8, RoundingMode::Down), "0.00001");
+        assert_eq!(format_amount(d("1.123456789"), 8, RoundingMode::Down), "1.12345678");
+        assert_eq!(format_amount(d("1.123456789"), 8, RoundingMode::Up), "1.12345679");
+        assert_eq!(format_amount(d("0.1234"), 2, RoundingMode::HalfEven), "0.12");
+    }
+
+    #[test]
+    fn format_amount_strips_trailing_zeros() {
+        assert_eq!(format_amount(d("1500.00"), 8, RoundingMode::Down), "1500");
+        assert_eq!(format_amount(d("1500"), 8, RoundingMode::Down), "1500");
+        assert_eq!(format_amount(d("0.10"), 8, RoundingMode::Down), "0.1");
+    }
+
+    #[test]
+    fn format_amount_never_writes_negative_zero() {
+        assert_eq!(format_amount(d("-0.000000001"), 8, RoundingMode::Down), "0");
+    }
+
+    #[test]
+    fn clock_skew_from_a_fixed_server_time() {
+        let skew = clock_skew(|| Ok(Utc::now() + chrono::Duration::seconds(5))).unwrap();
+        let error = (skew - chrono::Duration::seconds(5)).num_milliseconds().abs();
+        assert!(error < 100, "skew was {}", skew);
+    }
+
+    #[test]
+    fn clock_skew_passes_on_server_time_errors() {
+        let error = clock_skew(|| Err(format_err!("no server time"))).unwrap_err();
+        assert_eq!(error.to_string(), "no server time");
+    }
+}